@@ -0,0 +1,414 @@
+use keyberon::key_code::KeyCode;
+use keyberon::layout::Event;
+
+/// Actions the layout can trigger that keyberon doesn't handle itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomAction {
+    /// Types out a string, one chord of keycodes per character
+    TypeString(&'static [&'static [KeyCode]]),
+    /// Types a symbol that needs modifiers, like `!`, with its chord of
+    /// keycodes
+    Symbol(&'static [KeyCode]),
+    /// Makes a layer the default layer
+    DefaultLayer(usize),
+    /// Switches to a layer until it's toggled again
+    ToggleLayer(usize),
+    /// Switches to a layer for the next key press
+    OneShotLayer(usize),
+    /// Presses a media key, by its usage ID on the HID consumer page
+    Consumer(u16),
+    /// Presses a key that puts the host to sleep, powers it off or wakes it,
+    /// by its System Control usage ID on the HID generic desktop page
+    System(u8),
+    /// Moves the mouse while held, by -1, 0 or 1 in x and y
+    MouseMove(i8, i8),
+    /// Holds mouse buttons, as a bitmask with the left button as bit 0
+    MouseButton(u8),
+    /// Scrolls the wheel while held, by -1, 0 or 1 vertically and
+    /// horizontally
+    MouseWheel(i8, i8),
+    /// Slows the mouse down while held, for small movements
+    MousePrecision,
+}
+
+/// Types out the string from a `CustomAction::TypeString`. Each chord gets
+/// its own report, followed by an empty one so repeated characters register,
+/// and each report is kept until the host has it.
+#[derive(Default)]
+pub struct Typist {
+    chords: &'static [&'static [KeyCode]],
+    pressed: bool,
+}
+
+impl Typist {
+    pub const fn new() -> Self {
+        Self {
+            chords: &[],
+            pressed: false,
+        }
+    }
+
+    /// Starts typing a new string, dropping whatever was left of the last one
+    pub fn start(&mut self, chords: &'static [&'static [KeyCode]]) {
+        self.chords = chords;
+        self.pressed = false;
+    }
+
+    pub fn is_typing(&self) -> bool {
+        !self.chords.is_empty()
+    }
+
+    /// The keycodes to send in this report
+    pub fn report(&self) -> &'static [KeyCode] {
+        match self.chords.first() {
+            Some(chord) if !self.pressed => chord,
+            _ => &[],
+        }
+    }
+
+    /// Moves on to the next report, once the host has this one
+    pub fn sent(&mut self) {
+        match self.chords.split_first() {
+            Some(_) if !self.pressed => self.pressed = true,
+            Some((_, rest)) => {
+                self.chords = rest;
+                self.pressed = false;
+            }
+            None => (),
+        }
+    }
+}
+
+/// Types the chords from `CustomAction::Symbol`. A symbol's Shift and AltGr
+/// replace the held ones for just the report it's typed in, so a held Shift
+/// can't change it, and they don't stay held with the key. Ctrl, Alt and Gui
+/// stay held, so shortcuts with symbols work. That report is kept
+/// until the host has it, then one with the chord released, so the host
+/// never sees the chord held.
+#[derive(Default)]
+pub struct Symbols {
+    chord: &'static [KeyCode],
+    releasing: bool,
+}
+
+impl Symbols {
+    pub const fn new() -> Self {
+        Self {
+            chord: &[],
+            releasing: false,
+        }
+    }
+
+    pub fn press(&mut self, chord: &'static [KeyCode]) {
+        self.chord = chord;
+        self.releasing = false;
+    }
+
+    /// The keycodes to send in this report, given the keycodes of the held
+    /// keys
+    pub fn keycodes(&self, held: impl Iterator<Item = KeyCode>) -> impl Iterator<Item = KeyCode> {
+        let (chord, releasing) = (self.chord, self.releasing);
+        let pressed = match releasing {
+            true => &[],
+            false => chord,
+        };
+        held.filter(move |&k| match releasing {
+            true => !chord.contains(&k),
+            false => chord.is_empty() || !is_shift(k) || chord.contains(&k),
+        })
+        .chain(pressed.iter().copied())
+    }
+
+    /// Moves on from the chord to its release, then to the held keys, once
+    /// the host has the report
+    pub fn sent(&mut self) {
+        match self.releasing {
+            false if !self.chord.is_empty() => self.releasing = true,
+            _ => self.press(&[]),
+        }
+    }
+}
+
+/// Whether a key is one of the modifiers that change which character a key
+/// types, Shift and AltGr
+fn is_shift(k: KeyCode) -> bool {
+    matches!(k, KeyCode::LShift | KeyCode::RShift | KeyCode::RAlt)
+}
+
+/// Layer switching that keyberon doesn't do itself. The default layer is the
+/// layout's default layer, and a toggled or one-shot layer is held over it
+/// with the layout's switch for it, so `t` keys on it are the default layer's
+/// key.
+pub struct LayerState {
+    default: usize,
+    toggled: Option<usize>,
+    one_shot: Option<usize>,
+    /// The layer whose switch is held in the layout
+    held: Option<usize>,
+    /// The row the layout's switches start on, and how many are in a row
+    switches: usize,
+    cols: usize,
+}
+
+impl LayerState {
+    /// For a layout with the switch to layer `n` at `n` keys into its rows
+    /// from row `switches`, with `cols` keys in each row
+    pub const fn new(switches: usize, cols: usize) -> Self {
+        Self {
+            default: 0,
+            toggled: None,
+            one_shot: None,
+            held: None,
+            switches,
+            cols,
+        }
+    }
+
+    /// The layer that should be the layout's default layer
+    pub fn default_layer(&self) -> usize {
+        self.default
+    }
+
+    pub fn set_default(&mut self, layer: usize) {
+        self.default = layer;
+        self.toggled = None;
+    }
+
+    pub fn toggle(&mut self, layer: usize) {
+        self.toggled = match self.toggled {
+            Some(toggled) if toggled == layer => None,
+            _ => Some(layer),
+        };
+    }
+
+    pub fn one_shot(&mut self, layer: usize) {
+        self.one_shot = Some(layer);
+    }
+
+    /// Ends the one-shot layer once a key has been pressed on it
+    pub fn key_pressed(&mut self) {
+        self.one_shot = None;
+    }
+
+    /// The layout events that release the switch for the layer that was
+    /// held and press the one for the layer to hold now, if it's changed
+    pub fn events(&mut self) -> impl Iterator<Item = Event> {
+        let layer = self.one_shot.or(self.toggled);
+        let (release, press) = match layer == self.held {
+            true => (None, None),
+            false => (self.held, layer),
+        };
+        self.held = layer;
+
+        let release = release
+            .map(|l| self.switch(l))
+            .map(|(r, c)| Event::Release(r, c));
+        let press = press
+            .map(|l| self.switch(l))
+            .map(|(r, c)| Event::Press(r, c));
+        release.into_iter().chain(press)
+    }
+
+    /// Where the switch to a layer is in the layout
+    fn switch(&self, layer: usize) -> (u8, u8) {
+        let row = self.switches + layer / self.cols;
+        (row as u8, (layer % self.cols) as u8)
+    }
+}
+
+/// How many keys are kept track of for a report with room for one usage
+const HELD_KEYS: usize = 4;
+
+/// The key to send in a report with room for one usage, like the consumer
+/// control and system control reports. It's the last one pressed that's
+/// still held.
+#[derive(Default)]
+pub struct ReportKeys<U> {
+    /// The held keys, oldest first. Once it's full, pressing another forgets
+    /// the oldest.
+    held: [U; HELD_KEYS],
+    len: usize,
+    sent: U,
+}
+
+impl<U: Copy + Default + PartialEq> ReportKeys<U> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, usage: U) {
+        self.release(usage);
+        if self.len == HELD_KEYS {
+            self.held.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.held[self.len] = usage;
+        self.len += 1;
+    }
+
+    pub fn release(&mut self, usage: U) {
+        if let Some(i) = self.held[..self.len].iter().position(|&u| u == usage) {
+            self.held.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+    }
+
+    /// The usage to send, if it's changed since the last report that was
+    /// sent. 0 is no key.
+    pub fn report(&self) -> Option<U> {
+        let held = self.held[..self.len].last().copied().unwrap_or_default();
+        (held != self.sent).then_some(held)
+    }
+
+    /// Records that the host has the report with the usage
+    pub fn sent(&mut self, usage: U) {
+        self.sent = usage;
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn typist_test() {
+    let mut typist = Typist::new();
+    typist.start(&[&[KeyCode::LShift, KeyCode::A], &[KeyCode::A]]);
+
+    assert_eq!(typist.report(), &[KeyCode::LShift, KeyCode::A]);
+    assert_eq!(typist.report(), &[KeyCode::LShift, KeyCode::A]);
+    typist.sent();
+    assert_eq!(typist.report(), &[]);
+    typist.sent();
+    assert_eq!(typist.report(), &[KeyCode::A]);
+    typist.sent();
+    assert!(typist.is_typing());
+    assert_eq!(typist.report(), &[]);
+    typist.sent();
+    assert!(!typist.is_typing());
+}
+
+#[cfg(test)]
+#[test]
+fn symbols_test() {
+    use KeyCode::*;
+
+    let report = |symbols: &Symbols, held: &[KeyCode]| {
+        let mut keys = symbols.keycodes(held.iter().copied());
+        [(); 4].map(|_| keys.next())
+    };
+    let mut symbols = Symbols::new();
+    symbols.press(&[LShift, Kb1]);
+
+    // Ctrl is kept for the shortcut, but the held Shift isn't the chord's
+    let chord = [Some(LCtrl), Some(LShift), Some(Kb1), None];
+    assert_eq!(report(&symbols, &[LCtrl, RShift]), chord);
+    assert_eq!(report(&symbols, &[LCtrl, RShift]), chord);
+    symbols.sent();
+
+    // The chord's keys are let go of, even the held ones
+    let released = [Some(A), None, None, None];
+    assert_eq!(report(&symbols, &[LShift, A]), released);
+    assert_eq!(report(&symbols, &[LShift, A]), released);
+    symbols.sent();
+
+    assert_eq!(
+        report(&symbols, &[LShift, A]),
+        [Some(LShift), Some(A), None, None]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn layer_state_test() {
+    use keyberon::layout::Event::{Press, Release};
+
+    let mut layers = LayerState::new(5, 2);
+    layers.set_default(1);
+    assert_eq!(layers.default_layer(), 1);
+    layers.toggle(2);
+    assert!(layers.events().eq([Press(6, 0)]));
+    assert!(layers.events().eq([]));
+
+    layers.one_shot(3);
+    assert!(layers.events().eq([Release(6, 0), Press(6, 1)]));
+    layers.key_pressed();
+    assert!(layers.events().eq([Release(6, 1), Press(6, 0)]));
+
+    layers.toggle(2);
+    assert!(layers.events().eq([Release(6, 0)]));
+}
+
+#[cfg(test)]
+#[test]
+fn layer_state_trans_test() {
+    use keyberon::action::Action::{KeyCode as K, Layer, Trans};
+    use keyberon::layout::{Event::*, Layout};
+    use KeyCode::*;
+
+    // The second row is the switches
+    static LAYERS: keyberon::layout::Layers<2, 2, 2, CustomAction> = [
+        [[K(A), K(B)], [Layer(0), Layer(1)]],
+        [[Trans, K(C)], [Layer(0), Layer(1)]],
+    ];
+    /// Taps a key, giving its keycode
+    fn tap(
+        layout: &mut Layout<2, 2, 2, CustomAction>,
+        layers: &mut LayerState,
+        col: u8,
+    ) -> KeyCode {
+        layers.events().for_each(|e| layout.event(e));
+        layout.event(Press(0, col));
+        for _ in 0..3 {
+            layout.tick();
+        }
+        let keycode = layout.keycodes().next().unwrap_or(No);
+        layers.key_pressed();
+        layout.event(Release(0, col));
+        layout.tick();
+        keycode
+    }
+
+    let mut layout = Layout::new(&LAYERS);
+    let mut layers = LayerState::new(1, 2);
+    layers.toggle(1);
+    assert_eq!(tap(&mut layout, &mut layers, 0), A);
+    assert_eq!(tap(&mut layout, &mut layers, 1), C);
+
+    layers.toggle(1);
+    layers.one_shot(1);
+    assert_eq!(tap(&mut layout, &mut layers, 0), A);
+    assert_eq!(tap(&mut layout, &mut layers, 1), B);
+}
+
+#[cfg(test)]
+#[test]
+fn report_keys_test() {
+    let mut consumer = ReportKeys::<u16>::new();
+    assert_eq!(consumer.report(), None);
+
+    consumer.press(0xe9);
+    assert_eq!(consumer.report(), Some(0xe9));
+    assert_eq!(consumer.report(), Some(0xe9));
+    consumer.sent(0xe9);
+    assert_eq!(consumer.report(), None);
+
+    consumer.press(0xcd);
+    consumer.release(0xe9);
+    assert_eq!(consumer.report(), Some(0xcd));
+    consumer.sent(0xcd);
+    consumer.release(0xcd);
+    assert_eq!(consumer.report(), Some(0));
+    consumer.sent(0);
+
+    // Letting go of the last one pressed goes back to the one still held
+    consumer.press(0xe9);
+    consumer.press(0xea);
+    consumer.press(0xcd);
+    assert_eq!(consumer.report(), Some(0xcd));
+    consumer.sent(0xcd);
+    consumer.release(0xcd);
+    assert_eq!(consumer.report(), Some(0xea));
+    consumer.sent(0xea);
+    consumer.release(0xe9);
+    assert_eq!(consumer.report(), None);
+    consumer.release(0xea);
+    assert_eq!(consumer.report(), Some(0));
+}
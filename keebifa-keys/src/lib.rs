@@ -0,0 +1,10 @@
+//! The parts of the keebifa firmware that don't touch the hardware: the
+//! custom actions and what they send, the mouse keys and the keyboard
+//! reports. They're their own crate so their tests run on the host, with
+//! `cargo test --target` and the host's target triple.
+
+#![no_std]
+
+pub mod action;
+pub mod mouse;
+pub mod report;
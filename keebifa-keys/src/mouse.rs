@@ -0,0 +1,345 @@
+/// How the cursor speeds up while a movement key is held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    /// Speeds up by the same amount every ms
+    Linear,
+    /// Speeds up slowly at first, for small movements, then quickly
+    Quadratic,
+}
+
+/// Settings for the mouse keys. Speeds past 127000 pixels per second, a
+/// full report every ms, and times to max speed past a minute are cut down to
+/// those.
+#[derive(Debug, Clone, Copy)]
+pub struct MouseConfig {
+    /// Speed when a movement key is first pressed, in pixels per second
+    pub initial_speed: u32,
+    /// Speed once a movement key has been held for `time_to_max`, in pixels
+    /// per second
+    pub max_speed: u32,
+    /// How long it takes to get to `max_speed`, in ms
+    pub time_to_max: u32,
+    pub curve: Curve,
+    /// What the speed is divided by while the precision key is held
+    pub precision: u32,
+    /// How often the wheel scrolls by one notch while a wheel key is held, in
+    /// ms
+    pub wheel_interval: u32,
+}
+
+impl MouseConfig {
+    /// As fast as a report can move the cursor every ms
+    const MAX_SPEED: u32 = 127_000;
+    /// Longer than anyone holds a movement key for
+    const MAX_TIME_TO_MAX: u32 = 60_000;
+
+    /// The config with its speeds and time to max speed limited to what the
+    /// mouse keys can do, so working out the speed can't overflow
+    const fn clamped(self) -> Self {
+        const fn min(a: u32, b: u32) -> u32 {
+            if a < b {
+                a
+            } else {
+                b
+            }
+        }
+        Self {
+            initial_speed: min(self.initial_speed, Self::MAX_SPEED),
+            max_speed: min(self.max_speed, Self::MAX_SPEED),
+            time_to_max: min(self.time_to_max, Self::MAX_TIME_TO_MAX),
+            ..self
+        }
+    }
+}
+
+/// What to send in a mouse report. `y` is positive down, and `wheel` is
+/// positive up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    pub buttons: u8,
+    pub x: i8,
+    pub y: i8,
+    pub wheel: i8,
+    pub pan: i8,
+}
+
+/// Moves the cursor, presses the buttons and scrolls the wheel for the mouse
+/// keys that are held. It's ticked every ms, so how fast the cursor moves
+/// doesn't depend on how often the matrix is scanned.
+pub struct MouseKeys {
+    config: MouseConfig,
+    /// Direction of the held movement and wheel keys, added together
+    direction: (i8, i8),
+    wheel: (i8, i8),
+    buttons: u8,
+    precise: u8,
+    /// How long the movement keys have been held, in ms
+    moving: u32,
+    /// ms since the wheel last scrolled, while a wheel key is held
+    scrolling: u32,
+    /// Thousandths of a pixel moved but not yet sent
+    remainder: u32,
+    /// Movement and scrolling not yet sent, as much as a report can hold
+    pending: (i16, i16),
+    pending_wheel: (i16, i16),
+    sent_buttons: u8,
+}
+
+impl MouseKeys {
+    pub const fn new(config: MouseConfig) -> Self {
+        Self {
+            config: config.clamped(),
+            direction: (0, 0),
+            wheel: (0, 0),
+            buttons: 0,
+            precise: 0,
+            moving: 0,
+            scrolling: 0,
+            remainder: 0,
+            pending: (0, 0),
+            pending_wheel: (0, 0),
+            sent_buttons: 0,
+        }
+    }
+
+    pub fn press_move(&mut self, x: i8, y: i8) {
+        self.direction.0 += x;
+        self.direction.1 += y;
+    }
+
+    pub fn release_move(&mut self, x: i8, y: i8) {
+        self.direction.0 -= x;
+        self.direction.1 -= y;
+    }
+
+    pub fn press_wheel(&mut self, wheel: i8, pan: i8) {
+        self.wheel.0 += wheel;
+        self.wheel.1 += pan;
+    }
+
+    pub fn release_wheel(&mut self, wheel: i8, pan: i8) {
+        self.wheel.0 -= wheel;
+        self.wheel.1 -= pan;
+    }
+
+    pub fn press_buttons(&mut self, buttons: u8) {
+        self.buttons |= buttons;
+    }
+
+    pub fn release_buttons(&mut self, buttons: u8) {
+        self.buttons &= !buttons;
+    }
+
+    pub fn press_precision(&mut self) {
+        self.precise += 1;
+    }
+
+    pub fn release_precision(&mut self) {
+        self.precise = self.precise.saturating_sub(1);
+    }
+
+    /// The speed after the movement keys have been held for `moving` ms, in
+    /// pixels per second. It slows down instead if `max_speed` is the lower
+    /// one.
+    fn speed(&self) -> u32 {
+        let MouseConfig {
+            initial_speed,
+            max_speed,
+            time_to_max,
+            ..
+        } = self.config;
+        // The config's clamped, so none of this overflows an i64
+        let initial = initial_speed as i64;
+        let change = max_speed as i64 - initial;
+        let (t, time) = (self.moving.min(time_to_max) as i64, time_to_max as i64);
+        let speed = match self.config.curve {
+            _ if time_to_max == 0 => max_speed as i64,
+            Curve::Linear => initial + change * t / time,
+            Curve::Quadratic => initial + change * t * t / (time * time),
+        } as u32;
+        match self.precise {
+            0 => speed,
+            _ => speed / self.config.precision.max(1),
+        }
+    }
+
+    /// Moves and scrolls for the ms since the last tick
+    pub fn tick(&mut self) {
+        let (x, y) = (self.direction.0.signum(), self.direction.1.signum());
+        if (x, y) == (0, 0) {
+            self.moving = 0;
+            self.remainder = 0;
+        } else {
+            self.remainder += self.speed();
+            self.moving = self.moving.saturating_add(1);
+            let pixels = (self.remainder / 1000) as i16;
+            self.remainder %= 1000;
+            self.pending.0 = clamp(self.pending.0 + x as i16 * pixels);
+            self.pending.1 = clamp(self.pending.1 + y as i16 * pixels);
+        }
+
+        let (wheel, pan) = (self.wheel.0.signum(), self.wheel.1.signum());
+        if (wheel, pan) == (0, 0) {
+            self.scrolling = 0;
+        } else {
+            // A notch as soon as the key is pressed, then one every interval
+            if self.scrolling == 0 {
+                self.pending_wheel.0 = clamp(self.pending_wheel.0 + wheel as i16);
+                self.pending_wheel.1 = clamp(self.pending_wheel.1 + pan as i16);
+            }
+            self.scrolling = (self.scrolling + 1) % self.config.wheel_interval.max(1);
+        }
+    }
+
+    /// The report to send, if there's been movement, scrolling or a button
+    /// change since the last report that was sent
+    pub fn report(&self) -> Option<Report> {
+        let report = Report {
+            buttons: self.buttons,
+            x: self.pending.0 as i8,
+            y: self.pending.1 as i8,
+            wheel: self.pending_wheel.0 as i8,
+            pan: self.pending_wheel.1 as i8,
+        };
+        let idle = Report {
+            buttons: self.sent_buttons,
+            ..Report::default()
+        };
+        (report != idle).then_some(report)
+    }
+
+    /// Records that the host has the report, so its movement isn't sent
+    /// again
+    pub fn sent(&mut self, report: &Report) {
+        self.sent_buttons = report.buttons;
+        self.pending.0 -= report.x as i16;
+        self.pending.1 -= report.y as i16;
+        self.pending_wheel.0 -= report.wheel as i16;
+        self.pending_wheel.1 -= report.pan as i16;
+    }
+}
+
+/// Keeps movement that hasn't been sent to what fits in a report, so it
+/// doesn't build up while the host isn't taking them
+fn clamp(value: i16) -> i16 {
+    value.clamp(-127, 127)
+}
+
+#[cfg(test)]
+const TEST_CONFIG: MouseConfig = MouseConfig {
+    initial_speed: 1000,
+    max_speed: 3000,
+    time_to_max: 4,
+    curve: Curve::Linear,
+    precision: 4,
+    wheel_interval: 3,
+};
+
+/// Ticks the mouse keys for N ms, giving the report sent on each one
+#[cfg(test)]
+fn reports<const N: usize>(mouse: &mut MouseKeys) -> [Option<Report>; N] {
+    [(); N].map(|_| {
+        mouse.tick();
+        let report = mouse.report();
+        if let Some(report) = &report {
+            mouse.sent(report);
+        }
+        report
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn acceleration_test() {
+    let mut mouse = MouseKeys::new(TEST_CONFIG);
+    mouse.press_move(1, 0);
+    let xs = reports::<6>(&mut mouse).map(|r| r.map(|r| r.x));
+    assert_eq!(xs, [Some(1), Some(1), Some(2), Some(3), Some(3), Some(3)]);
+
+    // Letting go starts from the initial speed again
+    mouse.release_move(1, 0);
+    assert_eq!(reports::<2>(&mut mouse), [None, None]);
+    mouse.press_move(0, -1);
+    let moves = reports::<2>(&mut mouse).map(|r| r.map(|r| (r.x, r.y)));
+    assert_eq!(moves, [Some((0, -1)), Some((0, -1))]);
+}
+
+#[cfg(test)]
+#[test]
+fn quadratic_test() {
+    let mut mouse = MouseKeys::new(MouseConfig {
+        curve: Curve::Quadratic,
+        ..TEST_CONFIG
+    });
+    mouse.press_move(-1, 1);
+    let moves = reports::<6>(&mut mouse).map(|r| r.map(|r| (r.x, r.y)));
+    assert_eq!(
+        moves,
+        [(-1, 1), (-1, 1), (-1, 1), (-2, 2), (-3, 3), (-3, 3)].map(Some)
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn precision_test() {
+    let mut mouse = MouseKeys::new(TEST_CONFIG);
+    mouse.press_precision();
+    mouse.press_move(1, 0);
+    // A quarter of the speed, so it takes a few ms to move a pixel
+    let xs = reports::<8>(&mut mouse).map(|r| r.map(|r| r.x));
+    assert_eq!(
+        xs,
+        [None, None, Some(1), None, Some(1), Some(1), Some(1), None]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn buttons_and_wheel_test() {
+    let mut mouse = MouseKeys::new(TEST_CONFIG);
+    mouse.press_buttons(1);
+    mouse.press_wheel(1, 0);
+    let down = Some(Report {
+        buttons: 1,
+        wheel: 1,
+        ..Report::default()
+    });
+    assert_eq!(
+        reports::<7>(&mut mouse),
+        [down, None, None, down, None, None, down]
+    );
+
+    mouse.release_wheel(1, 0);
+    mouse.release_buttons(1);
+    assert_eq!(reports::<2>(&mut mouse), [Some(Report::default()), None]);
+}
+
+#[cfg(test)]
+#[test]
+fn limits_test() {
+    // Slows down, rather than overflowing, when the max speed is lower
+    let mut mouse = MouseKeys::new(MouseConfig {
+        initial_speed: 3000,
+        max_speed: 1000,
+        ..TEST_CONFIG
+    });
+    mouse.press_move(1, 0);
+    let xs = reports::<6>(&mut mouse).map(|r| r.map(|r| r.x));
+    assert_eq!(xs, [Some(3), Some(2), Some(2), Some(2), Some(1), Some(1)]);
+
+    // Too fast for too long is the fastest there is, and movement the host
+    // doesn't take is only kept up to what a report holds
+    let mut mouse = MouseKeys::new(MouseConfig {
+        max_speed: u32::MAX,
+        time_to_max: u32::MAX,
+        curve: Curve::Quadratic,
+        ..TEST_CONFIG
+    });
+    mouse.press_move(-1, 1);
+    mouse.press_wheel(1, 0);
+    for _ in 0..100_000 {
+        mouse.tick();
+    }
+    let report = mouse.report().unwrap();
+    assert_eq!((report.x, report.y, report.wheel), (-127, 127, 127));
+}
@@ -0,0 +1,154 @@
+use keyberon::key_code::KeyCode;
+
+/// The format the host has asked the boot keyboard for with SET_PROTOCOL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportProtocol {
+    /// The fixed 8 byte report from the HID spec, for BIOSes and other hosts
+    /// that don't read report descriptors
+    Boot = 0,
+    /// The report as the report descriptor has it
+    Report = 1,
+}
+
+/// The boot keyboard's report descriptor. It's the boot report, a byte of
+/// modifiers, a reserved byte and 6 keys, with the keys going up to the
+/// modifiers rather than stopping at Application like the boot protocol's.
+#[rustfmt::skip]
+pub const BOOT_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,       // Usage Page (Generic Desktop)
+    0x09, 0x06,       // Usage (Keyboard)
+    0xa1, 0x01,       // Collection (Application)
+    0x05, 0x07,       //   Usage Page (Keyboard/Keypad)
+    0x19, 0xe0,       //   Usage Minimum (Left Control)
+    0x29, 0xe7,       //   Usage Maximum (Right GUI)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x25, 0x01,       //   Logical Maximum (1)
+    0x75, 0x01,       //   Report Size (1)
+    0x95, 0x08,       //   Report Count (8)
+    0x81, 0x02,       //   Input (Data, Variable, Absolute)
+    0x95, 0x01,       //   Report Count (1)
+    0x75, 0x08,       //   Report Size (8)
+    0x81, 0x01,       //   Input (Constant)
+    0x05, 0x08,       //   Usage Page (LEDs)
+    0x19, 0x01,       //   Usage Minimum (Num Lock)
+    0x29, 0x05,       //   Usage Maximum (Kana)
+    0x95, 0x05,       //   Report Count (5)
+    0x75, 0x01,       //   Report Size (1)
+    0x91, 0x02,       //   Output (Data, Variable, Absolute)
+    0x95, 0x01,       //   Report Count (1)
+    0x75, 0x03,       //   Report Size (3)
+    0x91, 0x01,       //   Output (Constant)
+    0x05, 0x07,       //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00,       //   Usage Minimum (0)
+    0x29, 0xe7,       //   Usage Maximum (Right GUI)
+    0x15, 0x00,       //   Logical Minimum (0)
+    0x26, 0xe7, 0x00, //   Logical Maximum (231)
+    0x95, 0x06,       //   Report Count (6)
+    0x75, 0x08,       //   Report Size (8)
+    0x81, 0x00,       //   Input (Data, Array)
+    0xc0,             // End Collection
+];
+
+/// The N-key rollover keyboard's report descriptor. It's a bit for each
+/// usage on the keyboard page up to the modifiers, so any number of keys can
+/// be held.
+#[rustfmt::skip]
+pub const NKRO_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xa1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0xe7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0xe8, //   Report Count (232)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xc0,       // End Collection
+];
+
+/// An N-key rollover report, with the bit for each held keycode set.
+/// Keycodes past the modifiers, like keyberon's media keycodes, aren't in it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NkroReport([u8; 29]);
+
+impl NkroReport {
+    pub fn pressed(&mut self, kc: KeyCode) {
+        let usage = kc as usize;
+        if (KeyCode::A as usize..=KeyCode::RGui as usize).contains(&usage) {
+            self.0[usage / 8] |= 1 << (usage % 8);
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 29] {
+        &self.0
+    }
+
+    fn is_pressed(&self, usage: usize) -> bool {
+        self.0[usage / 8] & (1 << (usage % 8)) != 0
+    }
+
+    /// The same keys as an 8 byte boot keyboard report: a byte of modifiers,
+    /// a reserved byte, then 6 keys. With the boot protocol, keys the boot
+    /// keyboard doesn't have are left out. With more than 6 keys held, the
+    /// keys are all ErrorRollOver, rather than leaving some out and typing
+    /// the wrong thing.
+    pub fn boot_report(&self, protocol: ReportProtocol) -> [u8; 8] {
+        // One past the last key, as the modifiers aren't keys in the report
+        let end = match protocol {
+            ReportProtocol::Boot => KeyCode::Application as usize + 1,
+            ReportProtocol::Report => KeyCode::LCtrl as usize,
+        };
+        // The modifiers are the last byte of the bitmap, in the same order
+        let mut report = [self.0[28], 0, 0, 0, 0, 0, 0, 0];
+        let mut keys = (KeyCode::A as usize..end).filter(|&u| self.is_pressed(u));
+        for (slot, usage) in report[2..].iter_mut().zip(keys.by_ref()) {
+            *slot = usage as u8;
+        }
+        if keys.next().is_some() {
+            report[2..].fill(KeyCode::ErrorRollOver as u8);
+        }
+        report
+    }
+}
+
+impl core::iter::FromIterator<KeyCode> for NkroReport {
+    fn from_iter<T: IntoIterator<Item = KeyCode>>(iter: T) -> Self {
+        let mut report = Self::default();
+        iter.into_iter().for_each(|kc| report.pressed(kc));
+        report
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn boot_report_test() {
+    use KeyCode::*;
+    use ReportProtocol::*;
+
+    let report: NkroReport = [B, LShift, A, LCtrl, RAlt, F13].into_iter().collect();
+    assert_eq!(
+        report.boot_report(Report),
+        [0x43, 0, 0x04, 0x05, 0x68, 0, 0, 0]
+    );
+    assert_eq!(report.boot_report(Boot), [0x43, 0, 0x04, 0x05, 0, 0, 0, 0]);
+
+    let report: NkroReport = [A, B, LCtrl, C, D, E, F, G].into_iter().collect();
+    assert_eq!(report.boot_report(Boot), [0x01, 0, 1, 1, 1, 1, 1, 1]);
+}
+
+#[cfg(test)]
+#[test]
+fn nkro_report_test() {
+    use KeyCode::*;
+
+    let report: NkroReport = [A, B, Kb1, LShift, RGui, MediaPlayPause]
+        .into_iter()
+        .collect();
+    let mut bytes = [0; 29];
+    bytes[0] = 0b0011_0000;
+    bytes[3] = 0b0100_0000;
+    bytes[28] = 0b1000_0010;
+    assert_eq!(report.as_bytes(), &bytes);
+}
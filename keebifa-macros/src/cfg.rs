@@ -0,0 +1,248 @@
+//! `#[cfg(feature = "...")]` on keys, rows and statements of a layout. Proc
+//! macros can't tell which features are enabled, so the layout is expanded
+//! once for each combination of the features it uses, each behind a `cfg`
+//! for that combination.
+
+use proc_macro2::{token_stream::IntoIter, Delimiter, Span, TokenStream, TokenTree};
+use proc_macro_error::Diagnostic;
+use quote::quote;
+use std::cell::RefCell;
+use std::iter::Peekable;
+
+/// The most features a layout can use, as it's expanded for every
+/// combination of them
+const MAX_FEATURES: usize = 6;
+
+thread_local! {
+    /// The features enabled in the expansion being parsed
+    static ENABLED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    /// The errors emitted so far, so the ones in every expansion are only
+    /// emitted once
+    static EMITTED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A `cfg` predicate
+enum Cfg {
+    Feature(String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    fn parse(tokens: TokenStream, span: Span) -> Cfg {
+        let mut tokens = tokens.into_iter();
+        let cfg = match (tokens.next(), tokens.next(), tokens.next()) {
+            (Some(TokenTree::Ident(i)), Some(TokenTree::Punct(p)), Some(TokenTree::Literal(l)))
+                if i == "feature" && p.as_char() == '=' =>
+            {
+                match crate::string_value(&l.to_string()) {
+                    Some(feature) => Cfg::Feature(feature),
+                    None => abort!(l, "Expected a feature name: feature = \"name\""),
+                }
+            }
+            (Some(TokenTree::Ident(i)), Some(TokenTree::Group(g)), None)
+                if g.delimiter() == Delimiter::Parenthesis =>
+            {
+                let mut inner = Vec::new();
+                let mut predicate = TokenStream::new();
+                for t in g.stream() {
+                    match t {
+                        TokenTree::Punct(p) if p.as_char() == ',' => {
+                            inner.push(Cfg::parse(std::mem::take(&mut predicate), g.span()))
+                        }
+                        t => predicate.extend(Some(t)),
+                    }
+                }
+                if !predicate.is_empty() {
+                    inner.push(Cfg::parse(predicate, g.span()));
+                }
+                match i.to_string().as_str() {
+                    "all" => Cfg::All(inner),
+                    "any" => Cfg::Any(inner),
+                    "not" if inner.len() == 1 => Cfg::Not(Box::new(inner.remove(0))),
+                    "not" => abort!(g, "Expected one predicate in `not(...)`"),
+                    _ => {
+                        abort!(i, "Unknown predicate `{}`", i; help = "Layouts can use `feature = \"name\"`, `all()`, `any()` and `not()`")
+                    }
+                }
+            }
+            _ => {
+                abort!(span, "Expected a feature: #[cfg(feature = \"name\")]"; help = "Layouts can use `feature = \"name\"`, `all()`, `any()` and `not()`")
+            }
+        };
+        if let Some(t) = tokens.next() {
+            abort!(t, "Unexpected token after the predicate");
+        }
+        cfg
+    }
+
+    fn is_enabled(&self, enabled: &[String]) -> bool {
+        match self {
+            Cfg::Feature(feature) => enabled.contains(feature),
+            Cfg::All(all) => all.iter().all(|cfg| cfg.is_enabled(enabled)),
+            Cfg::Any(any) => any.iter().any(|cfg| cfg.is_enabled(enabled)),
+            Cfg::Not(cfg) => !cfg.is_enabled(enabled),
+        }
+    }
+
+    fn features(&self, out: &mut Vec<String>) {
+        match self {
+            Cfg::Feature(feature) if !out.contains(feature) => out.push(feature.clone()),
+            Cfg::Feature(_) => (),
+            Cfg::All(inner) | Cfg::Any(inner) => inner.iter().for_each(|cfg| cfg.features(out)),
+            Cfg::Not(cfg) => cfg.features(out),
+        }
+    }
+}
+
+/// Whether a token is the `[cfg(...)]` of an attribute. A `#` followed by
+/// anything else is the `#` key.
+fn is_attribute(t: Option<&TokenTree>) -> bool {
+    match t {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+            matches!(g.stream().into_iter().next(), Some(TokenTree::Ident(i)) if i == "cfg")
+        }
+        _ => false,
+    }
+}
+
+/// Parses the `[cfg(...)]` of an attribute
+fn parse_attribute(attribute: &TokenTree) -> Cfg {
+    let mut tokens = match attribute {
+        TokenTree::Group(g) => g.stream().into_iter(),
+        _ => unreachable!(),
+    };
+    match (tokens.next(), tokens.next(), tokens.next()) {
+        (Some(_), Some(TokenTree::Group(g)), None) if g.delimiter() == Delimiter::Parenthesis => {
+            Cfg::parse(g.stream(), g.span())
+        }
+        _ => abort!(attribute, "Expected a feature: #[cfg(feature = \"name\")]"),
+    }
+}
+
+/// The features used by the `#[cfg(...)]`s anywhere in a layout
+pub fn features(input: &TokenStream) -> Vec<String> {
+    fn find(input: &TokenStream, out: &mut Vec<String>) {
+        let mut tokens = input.clone().into_iter().peekable();
+        while let Some(t) = tokens.next() {
+            match t {
+                TokenTree::Punct(p) if p.as_char() == '#' && is_attribute(tokens.peek()) => {
+                    parse_attribute(&tokens.next().unwrap()).features(out);
+                }
+                TokenTree::Group(g) => find(&g.stream(), out),
+                _ => (),
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    find(input, &mut out);
+    if out.len() > MAX_FEATURES {
+        proc_macro_error::abort_call_site!(
+            "A layout can use at most {} features, this one uses {}",
+            MAX_FEATURES,
+            out.len()
+        );
+    }
+    out
+}
+
+/// Every combination of enabled features, starting with none of them
+pub fn combinations(features: &[String]) -> Vec<Vec<String>> {
+    (0..1 << features.len())
+        .map(|bits: usize| {
+            let enabled = features.iter().enumerate();
+            enabled
+                .filter(|(i, _)| bits & (1 << i) != 0)
+                .map(|(_, feature)| feature.clone())
+                .collect()
+        })
+        .collect()
+}
+
+/// Starts expanding the layout for a combination of features
+pub fn enable(enabled: &[String]) {
+    ENABLED.with(|e| *e.borrow_mut() = enabled.to_vec());
+}
+
+/// Forgets the errors emitted by the last layout
+pub fn reset() {
+    EMITTED.with(|e| e.borrow_mut().clear());
+    enable(&[]);
+}
+
+/// The `#[cfg(...)]` for the expansion with the enabled features, out of all
+/// of the features the layout uses
+pub fn attribute(features: &[String]) -> TokenStream {
+    if features.is_empty() {
+        return TokenStream::new();
+    }
+    let enabled = ENABLED.with(|e| e.borrow().clone());
+    let features = features
+        .iter()
+        .map(|feature| match enabled.contains(feature) {
+            true => quote! { feature = #feature },
+            false => quote! { not(feature = #feature) },
+        });
+    quote! { #[cfg(all(#(#features),*))] }
+}
+
+/// `+feature` for each enabled feature, to tell the drawings of each
+/// expansion apart
+pub fn suffix() -> String {
+    ENABLED.with(|e| e.borrow().iter().map(|f| format!("+{}", f)).collect())
+}
+
+/// Parses any `#[cfg(...)]`s before the next key, row or statement, and
+/// gives whether it's in the expansion being parsed
+pub fn is_enabled(tokens: &mut Peekable<IntoIter>) -> bool {
+    let mut enabled = true;
+    loop {
+        let mut ahead = tokens.clone();
+        match ahead.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '#' && is_attribute(ahead.peek()) => (),
+            _ => break,
+        }
+        let hash = tokens.next().unwrap();
+        let cfg = parse_attribute(&tokens.next().unwrap());
+        if tokens.peek().is_none() {
+            abort!(hash, "Expected something for the attribute to be on");
+        }
+        enabled &= ENABLED.with(|e| cfg.is_enabled(&e.borrow()));
+    }
+    enabled
+}
+
+/// Whether an error hasn't already been emitted by another expansion of the
+/// layout
+pub fn first_time(span: Span, diagnostic: &Diagnostic) -> bool {
+    let location = crate::file::location().unwrap_or_default();
+    let key = format!("{:?} {} {}", span, location, diagnostic.message());
+    EMITTED.with(|e| {
+        let mut emitted = e.borrow_mut();
+        let first = !emitted.contains(&key);
+        if first {
+            emitted.push(key);
+        }
+        first
+    })
+}
+
+/// Adds the features the layout is being expanded with to an error, as the
+/// errors that are in every expansion are emitted by the one without any
+pub fn note(diagnostic: Diagnostic) -> Diagnostic {
+    let enabled = ENABLED.with(|e| e.borrow().clone());
+    match enabled.is_empty() {
+        true => diagnostic,
+        false => diagnostic.note(format!("when built with {}", features_list(&enabled))),
+    }
+}
+
+fn features_list(enabled: &[String]) -> String {
+    let names: Vec<String> = enabled.iter().map(|f| format!("`{}`", f)).collect();
+    match names.len() {
+        1 => format!("feature {}", names[0]),
+        _ => format!("features {}", names.join(", ")),
+    }
+}
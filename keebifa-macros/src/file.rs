@@ -0,0 +1,314 @@
+//! Layouts written in a TOML file, for `include_layout!`. The keys are written
+//! the same way as in `alice_layout!`, and errors in them say where in the
+//! file they are.
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use proc_macro_error::Diagnostic;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use toml::Spanned;
+
+thread_local! {
+    /// Where the keys being parsed are written in a layout file, if they are
+    static LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Sets where the keys being parsed are written, for the errors in them
+pub fn at(location: Option<String>) {
+    LOCATION.with(|l| *l.borrow_mut() = location);
+}
+
+/// Where the keys being parsed are written, if they're from a layout file
+pub fn location() -> Option<String> {
+    LOCATION.with(|l| l.borrow().clone())
+}
+
+/// Adds where the keys being parsed are written to an error about them
+pub fn locate(diagnostic: Diagnostic) -> Diagnostic {
+    match location() {
+        Some(location) => diagnostic.note(format!("at {}", location)),
+        None => diagnostic,
+    }
+}
+
+/// Some of the keys of a layout, and where they're written in the layout file
+pub struct Piece {
+    pub tokens: TokenStream,
+    pub location: Option<String>,
+}
+
+pub struct Layout {
+    pub host: Option<Piece>,
+    pub aliases: Vec<(String, Piece)>,
+    pub layers: Vec<Layer>,
+}
+
+pub struct Layer {
+    pub name: Option<(String, String)>,
+    pub parent: Option<(String, String)>,
+    /// The rows of keys, or for a layer with a parent, one row with each
+    /// key it changes
+    pub rows: Vec<Vec<Piece>>,
+    pub location: String,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    host: Option<Spanned<String>>,
+    #[serde(default)]
+    alias: BTreeMap<String, Spanned<String>>,
+    #[serde(default)]
+    layer: Vec<FileLayer>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileLayer {
+    name: Option<Spanned<String>>,
+    parent: Option<Spanned<String>>,
+    description: Option<String>,
+    keys: Spanned<String>,
+}
+
+/// Reads a layout file. `name` is the path as it's written in the layout, for
+/// the errors.
+pub fn read_layout(path: &Path, name: &str, span: Span) -> Layout {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => abort!(span, "Couldn't read {}: {}", path.display(), e),
+    };
+    let source = Source {
+        name,
+        text: &text,
+        span,
+    };
+    let file: File = match toml::from_str(&text) {
+        Ok(file) => file,
+        Err(e) => {
+            let location = e.span().map(|s| source.location(s.start));
+            abort!(span, "{} isn't a valid layout: {}", name, e.message(); note = "at {}", location.unwrap_or_else(|| name.to_string()))
+        }
+    };
+
+    let host = file
+        .host
+        .map(|host| source.piece(&host, &format!("{:?};", host.get_ref())));
+    let aliases = file
+        .alias
+        .iter()
+        .map(|(alias, key)| (alias.clone(), source.piece(key, key.get_ref())))
+        .collect();
+
+    let layers = file
+        .layer
+        .iter()
+        .map(|layer| {
+            let keys = layer.keys.get_ref();
+            let at = |offset| source.raw_offset(layer.keys.span(), offset);
+            let rows = match layer.parent {
+                Some(_) => vec![split(keys, |c| c == ',' || c == '\n')
+                    .map(|(offset, key)| source.piece_at(at(offset), key))
+                    .collect()],
+                None => split(keys, |c| c == '\n')
+                    .map(|(offset, row)| {
+                        join_labels(row, split(row, char::is_whitespace))
+                            .map(|(o, key)| source.piece_at(at(offset + o), key))
+                            .collect()
+                    })
+                    .collect(),
+            };
+            let name = |n: &Spanned<String>| (n.get_ref().clone(), source.location(n.span().start));
+            Layer {
+                name: layer.name.as_ref().map(name),
+                parent: layer.parent.as_ref().map(name),
+                rows,
+                location: source.location(layer.keys.span().start),
+                description: layer.description.clone(),
+            }
+        })
+        .collect();
+
+    Layout {
+        host,
+        aliases,
+        layers,
+    }
+}
+
+/// The text of a layout file, for working out where things are written in it
+struct Source<'a> {
+    name: &'a str,
+    text: &'a str,
+    span: Span,
+}
+
+impl Source<'_> {
+    /// The file, line and column of a byte offset, e.g. `keymap.toml:3:5`
+    fn location(&self, offset: usize) -> String {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        format!("{}:{}:{}", self.name, line, column)
+    }
+
+    /// Where the contents of a string start, after the quotes and the new
+    /// line a multi-line string starts with
+    fn content_start(&self, span: Range<usize>) -> usize {
+        let raw = &self.text[span.clone()];
+        let quotes = match raw.starts_with("'''") || raw.starts_with("\"\"\"") {
+            true => 3,
+            false => 1,
+        };
+        let rest = &raw[quotes..];
+        let newline = match (rest.starts_with('\n'), rest.starts_with("\r\n")) {
+            (true, _) => 1,
+            (_, true) => 2,
+            _ => 0,
+        };
+        span.start + quotes + newline
+    }
+
+    /// Where a byte offset in a string's value is written in the file.
+    /// Escapes and `\r\n` line ends are shorter in the value than they're
+    /// written, so this goes through the string a character at a time.
+    fn raw_offset(&self, span: Range<usize>, offset: usize) -> usize {
+        let basic = self.text[span.clone()].starts_with('"');
+        let mut raw = self.content_start(span.clone());
+        let mut value = 0;
+        while value < offset && raw < span.end {
+            let rest = &self.text[raw..];
+            let (written, decoded) = match rest.chars().next() {
+                Some('\r') if rest.starts_with("\r\n") => (2, 1),
+                Some('\\') if basic => escape(&rest[1..]),
+                Some(c) => (c.len_utf8(), c.len_utf8()),
+                None => break,
+            };
+            raw += written;
+            value += decoded;
+        }
+        raw
+    }
+
+    fn piece(&self, value: &Spanned<String>, text: &str) -> Piece {
+        self.piece_at(self.content_start(value.span()), text)
+    }
+
+    fn piece_at(&self, offset: usize, text: &str) -> Piece {
+        let location = self.location(offset);
+        let tokens = match text.parse() {
+            Ok(tokens) => tokens,
+            Err(_) => {
+                at(Some(location));
+                abort!(self.span, "Couldn't read the key `{}`", text)
+            }
+        };
+        Piece {
+            tokens: respan(tokens, self.span),
+            location: Some(location),
+        }
+    }
+}
+
+/// How long an escape in a basic string is as it's written, including its
+/// `\`, and in the string's value. `rest` is what's after the `\`.
+fn escape(rest: &str) -> (usize, usize) {
+    let unicode = |digits: usize| {
+        let c = rest
+            .get(1..=digits)
+            .and_then(|d| u32::from_str_radix(d, 16).ok());
+        let decoded = c.and_then(char::from_u32).map_or(1, char::len_utf8);
+        (2 + digits, decoded)
+    };
+    match rest.chars().next() {
+        Some('u') => unicode(4),
+        Some('U') => unicode(8),
+        // A `\` at the end of a line leaves out the line end and the
+        // whitespace after it
+        Some(c) if c.is_whitespace() => (1 + rest.len() - rest.trim_start().len(), 0),
+        _ => (2, 1),
+    }
+}
+
+/// Gives every token the span of the file's path, so errors in the keys
+/// point at it
+pub fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut t| {
+            if let TokenTree::Group(g) = &t {
+                let mut group = Group::new(g.delimiter(), respan(g.stream(), span));
+                group.set_span(span);
+                t = TokenTree::Group(group);
+            }
+            t.set_span(span);
+            t
+        })
+        .collect()
+}
+
+/// Joins the `@ "label"` after a key back onto it, as the spaces around the
+/// `@` split them apart
+fn join_labels<'a>(
+    row: &'a str,
+    keys: impl Iterator<Item = (usize, &'a str)>,
+) -> impl Iterator<Item = (usize, &'a str)> {
+    let mut joined: Vec<(usize, &str)> = Vec::new();
+    let mut keys = keys;
+    while let Some((offset, key)) = keys.next() {
+        let mut end = offset + key.len();
+        if key == "@" {
+            end = keys.next().map_or(end, |(o, k)| o + k.len());
+        }
+        match joined.last_mut() {
+            Some((start, last)) if key.starts_with('@') => *last = &row[*start..end],
+            _ => joined.push((offset, &row[offset..end])),
+        }
+    }
+    joined.into_iter()
+}
+
+/// Splits keys at the characters accepted by `at` that aren't in brackets,
+/// quotes or a character, with the offset of each. Empty pieces are left out.
+fn split(text: &str, at: impl Fn(char) -> bool) -> impl Iterator<Item = (usize, &str)> {
+    let mut pieces = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '\'' | '"' => {
+                // Skip to the closing quote, past any escaped characters
+                while let Some((_, next)) = chars.next() {
+                    match next {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if next == c => break,
+                        _ => (),
+                    }
+                }
+            }
+            _ if depth == 0 && at(c) => {
+                pieces.push((start, &text[start..i]));
+                start = i + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    pieces.push((start, &text[start..]));
+
+    pieces.into_iter().filter_map(|(start, piece)| {
+        let trimmed = piece.trim_start();
+        let start = start + piece.len() - trimmed.len();
+        let trimmed = trimmed.trim_end();
+        (!trimmed.is_empty()).then_some((start, trimmed))
+    })
+}
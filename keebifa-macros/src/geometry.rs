@@ -0,0 +1,151 @@
+//! The physical shapes of the keyboards a layout can be for: how many keys
+//! are in each row, and how wide they are for drawing them
+
+/// Width of each key of a preset in key units, by row. Negative widths are
+/// gaps between keys.
+type Widths = &'static [&'static [f32]];
+
+#[rustfmt::skip]
+const ALICE: Widths = &[
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0],
+    &[1.0, 1.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.5],
+    &[1.0, 1.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.25],
+    &[2.25, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.75, 1.0],
+    &[1.25, 1.5, 2.25, 1.25, 2.75, 1.5, 1.5],
+];
+
+#[rustfmt::skip]
+const ANSI_60: Widths = &[
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0],
+    &[1.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.5],
+    &[1.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.25],
+    &[2.25, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.75],
+    &[1.25, 1.25, 1.25, 6.25, 1.25, 1.25, 1.25, 1.25],
+];
+
+#[rustfmt::skip]
+const ORTHO_5X12: Widths = &[
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+];
+
+#[rustfmt::skip]
+const TKL: Widths = &[
+    &[1.0, -1.0, 1.0, 1.0, 1.0, 1.0, -0.5, 1.0, 1.0, 1.0, 1.0, -0.5, 1.0, 1.0, 1.0, 1.0, -0.25, 1.0, 1.0, 1.0],
+    &[1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0, -0.25, 1.0, 1.0, 1.0],
+    &[1.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.5, -0.25, 1.0, 1.0, 1.0],
+    &[1.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.25],
+    &[2.25, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.75, -1.25, 1.0],
+    &[1.25, 1.25, 1.25, 6.25, 1.25, 1.25, 1.25, 1.25, -0.25, 1.0, 1.0, 1.0],
+];
+
+/// The Alice layout's halves: how many keys of each row are on the left
+/// half, and where each row starts, for the rows without a key in the macro
+/// column
+const ALICE_LEFT: [usize; 5] = [8, 7, 7, 6, 4];
+const ALICE_INDENT: [f32; 5] = [0.0, 0.0, 0.0, 1.0, 1.0];
+
+/// The built in geometries, by the name they're given with `geometry`
+const PRESETS: &[(&str, &str, Widths)] = &[
+    ("alice", "the Alice layout", ALICE),
+    ("ansi60", "a 60% ANSI layout", ANSI_60),
+    ("ortho5x12", "a 5x12 ortholinear layout", ORTHO_5X12),
+    ("tkl", "a TKL layout", TKL),
+];
+
+/// The shape of a keyboard
+pub struct Geometry {
+    /// What the keyboard is called in messages, e.g. "the Alice layout"
+    pub name: String,
+    /// Width of each key and gap, by row
+    widths: Vec<Vec<f32>>,
+    /// Whether it's the Alice layout, which is drawn as two turned halves
+    split: bool,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry::preset("alice").unwrap()
+    }
+}
+
+/// Where a key is drawn, in key units
+pub struct Place {
+    pub row: usize,
+    pub x: f32,
+    pub width: f32,
+    /// Which half of a split keyboard the key is on
+    pub left: bool,
+}
+
+impl Geometry {
+    pub fn preset(name: &str) -> Option<Geometry> {
+        let &(_, description, widths) = PRESETS.iter().find(|(n, _, _)| *n == name)?;
+        Some(Geometry {
+            name: description.to_string(),
+            widths: widths.iter().map(|row| row.to_vec()).collect(),
+            split: name == "alice",
+        })
+    }
+
+    /// A geometry with the given number of keys in each row, all 1u wide
+    pub fn rows(rows: &[usize]) -> Geometry {
+        Geometry {
+            name: "the declared geometry".to_string(),
+            widths: rows.iter().map(|&len| vec![1.0; len]).collect(),
+            split: false,
+        }
+    }
+
+    /// The names of the presets, for error messages
+    pub fn presets() -> String {
+        let names: Vec<String> = PRESETS.iter().map(|(n, _, _)| format!("`{}`", n)).collect();
+        names.join(", ")
+    }
+
+    /// Number of keys in each row
+    pub fn shape(&self) -> Vec<usize> {
+        self.widths
+            .iter()
+            .map(|row| row.iter().filter(|&&w| w > 0.0).count())
+            .collect()
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split
+    }
+
+    /// The place of every key, in the order they're written
+    pub fn places(&self) -> Vec<Place> {
+        // Space between the halves of a split keyboard, in key units
+        const GAP: f32 = 1.0;
+
+        let mut places = Vec::new();
+        for (row, widths) in self.widths.iter().enumerate() {
+            let mut x = match self.split {
+                true => ALICE_INDENT[row],
+                false => 0.0,
+            };
+            for &width in widths {
+                let i = places.iter().filter(|p: &&Place| p.row == row).count();
+                let left = !self.split || i < ALICE_LEFT[row];
+                if self.split && i == ALICE_LEFT[row] && width > 0.0 {
+                    x += GAP;
+                }
+                if width > 0.0 {
+                    places.push(Place {
+                        row,
+                        x,
+                        width,
+                        left,
+                    });
+                }
+                x += width.abs();
+            }
+        }
+        places
+    }
+}
@@ -0,0 +1,137 @@
+//! The keyboard layouts the host can be set to, for working out which keys
+//! type a character
+
+/// The keys that type characters, in the order of the rows in `HostLayout`
+#[rustfmt::skip]
+const KEYS: [&[&str]; 4] = [
+    &["Grave", "Kb1", "Kb2", "Kb3", "Kb4", "Kb5", "Kb6", "Kb7", "Kb8", "Kb9", "Kb0", "Minus", "Equal"],
+    &["Q", "W", "E", "R", "T", "Y", "U", "I", "O", "P", "LBracket", "RBracket", "Bslash"],
+    &["A", "S", "D", "F", "G", "H", "J", "K", "L", "SColon", "Quote", "NonUsHash"],
+    &["NonUsBslash", "Z", "X", "C", "V", "B", "N", "M", "Comma", "Dot", "Slash"],
+];
+
+/// The characters typed by each key in `KEYS` on its own, with Shift and with
+/// AltGr. A space means the key doesn't type anything.
+struct HostLayout {
+    name: &'static str,
+    base: [&'static str; 4],
+    shift: [&'static str; 4],
+    alt_gr: [&'static str; 4],
+    /// Characters typed with dead keys, which need Space after them
+    dead: &'static str,
+}
+
+#[rustfmt::skip]
+const LAYOUTS: &[HostLayout] = &[
+    HostLayout {
+        name: "en-US",
+        base:   ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;' ", " zxcvbnm,./"],
+        shift:  ["~!@#$%^&*()_+", "QWERTYUIOP{}|", "ASDFGHJKL:\" ", " ZXCVBNM<>?"],
+        alt_gr: ["             ", "             ", "            ", "           "],
+        dead: "",
+    },
+    HostLayout {
+        name: "en-GB",
+        base:   ["`1234567890-=", "qwertyuiop[] ", "asdfghjkl;'#", "\\zxcvbnm,./"],
+        shift:  ["¬!\"£$%^&*()_+", "QWERTYUIOP{} ", "ASDFGHJKL:@~", "|ZXCVBNM<>?"],
+        alt_gr: ["¦   €        ", "             ", "            ", "           "],
+        dead: "",
+    },
+    HostLayout {
+        name: "de-DE",
+        base:   ["^1234567890ß´", "qwertzuiopü+ ", "asdfghjklöä#", "<yxcvbnm,.-"],
+        shift:  ["°!\"§$%&/()=?`", "QWERTZUIOPÜ* ", "ASDFGHJKLÖÄ'", ">YXCVBNM;:_"],
+        alt_gr: ["  ²³   {[]}\\ ", "@ €        ~ ", "            ", "|      µ   "],
+        dead: "^´`",
+    },
+    HostLayout {
+        name: "fr-FR",
+        base:   ["²&é\"'(-è_çà)=", "azertyuiop^$ ", "qsdfghjklmù*", "<wxcvbn,;:!"],
+        shift:  [" 1234567890°+", "AZERTYUIOP¨£ ", "QSDFGHJKLM%µ", ">WXCVBN?./§"],
+        alt_gr: ["  ~#{[|`\\^@]}", "  €        ¤ ", "            ", "           "],
+        dead: "^¨~`",
+    },
+];
+
+/// The layout the host is set to, which decides the keys that type a
+/// character
+#[derive(Clone, Copy)]
+pub struct Host(&'static HostLayout);
+
+impl Default for Host {
+    fn default() -> Self {
+        Host(&LAYOUTS[0])
+    }
+}
+
+/// Keys pressed together to type a character
+pub struct Chord {
+    pub key: &'static str,
+    pub shift: bool,
+    pub alt_gr: bool,
+}
+
+impl Host {
+    pub fn from_name(name: &str) -> Option<Host> {
+        LAYOUTS.iter().find(|l| l.name == name).map(Host)
+    }
+
+    /// The names of every host layout, for error messages
+    pub fn names() -> String {
+        let names: Vec<String> = LAYOUTS.iter().map(|l| format!("`{}`", l.name)).collect();
+        names.join(", ")
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name
+    }
+
+    /// Whether a character is typed with a dead key, so needs more than one
+    /// chord
+    pub fn is_dead(&self, c: char) -> bool {
+        self.0.dead.contains(c)
+    }
+
+    /// The chords that type a character, or `None` if it can't be typed
+    pub fn chords(&self, c: char) -> Option<Vec<Chord>> {
+        let plain = |key| Chord {
+            key,
+            shift: false,
+            alt_gr: false,
+        };
+        let key = match c {
+            ' ' => "Space",
+            '\n' => "Enter",
+            '\t' => "Tab",
+            _ => {
+                let chord = self.find(c)?;
+                return Some(match self.is_dead(c) {
+                    true => vec![chord, plain("Space")],
+                    false => vec![chord],
+                });
+            }
+        };
+        Some(vec![plain(key)])
+    }
+
+    /// The chord for the key that types a character
+    fn find(&self, c: char) -> Option<Chord> {
+        let layers = [
+            (&self.0.base, false, false),
+            (&self.0.shift, true, false),
+            (&self.0.alt_gr, false, true),
+        ];
+        for (rows, shift, alt_gr) in layers {
+            for (keys, row) in KEYS.iter().zip(rows) {
+                if let Some(i) = row.chars().position(|k| k == c) {
+                    return Some(Chord {
+                        key: keys[i],
+                        shift,
+                        alt_gr,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,170 @@
+//! Names of the keycodes in `keyberon::key_code::KeyCode`, for checking the
+//! keycodes in a layout before rustc does
+
+/// Every variant of `keyberon::key_code::KeyCode`
+#[rustfmt::skip]
+const KEYCODES: &[&str] = &[
+    "No", "ErrorRollOver", "PostFail", "ErrorUndefined",
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S",
+    "T", "U", "V", "W", "X", "Y", "Z",
+    "Kb1", "Kb2", "Kb3", "Kb4", "Kb5", "Kb6", "Kb7", "Kb8", "Kb9", "Kb0",
+    "Enter", "Escape", "BSpace", "Tab", "Space", "Minus", "Equal", "LBracket", "RBracket",
+    "Bslash", "NonUsHash", "SColon",
+    "Quote", "Grave", "Comma", "Dot", "Slash", "CapsLock",
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+    "PScreen", "ScrollLock", "Pause", "Insert", "Home", "PgUp", "Delete", "End", "PgDown", "Right",
+    "Left", "Down", "Up",
+    "NumLock", "KpSlash", "KpAsterisk", "KpMinus", "KpPlus", "KpEnter",
+    "Kp1", "Kp2", "Kp3", "Kp4", "Kp5", "Kp6", "Kp7", "Kp8", "Kp9", "Kp0", "KpDot",
+    "NonUsBslash", "Application", "Power", "KpEqual",
+    "F13", "F14", "F15", "F16", "F17", "F18", "F19", "F20", "F21", "F22", "F23", "F24",
+    "Execute", "Help", "Menu", "Select", "Stop", "Again", "Undo", "Cut", "Copy", "Paste", "Find",
+    "Mute", "VolUp", "VolDown",
+    "LockingCapsLock", "LockingNumLock", "LockingScrollLock", "KpComma", "KpEqualSign",
+    "Intl1", "Intl2", "Intl3", "Intl4", "Intl5", "Intl6", "Intl7", "Intl8", "Intl9",
+    "Lang1", "Lang2", "Lang3", "Lang4", "Lang5", "Lang6", "Lang7", "Lang8", "Lang9",
+    "AltErase", "SysReq", "Cancel", "Clear", "Prior", "Return", "Separator", "Out", "Oper",
+    "ClearAgain", "CrSel", "ExSel",
+    "LCtrl", "LShift", "LAlt", "LGui", "RCtrl", "RShift", "RAlt", "RGui",
+    "MediaPlayPause", "MediaStopCD", "MediaPreviousSong", "MediaNextSong", "MediaEjectCD",
+    "MediaVolUp", "MediaVolDown", "MediaMute", "MediaWWW", "MediaBack", "MediaForward",
+    "MediaStop",
+    "MediaFind", "MediaScrollUp", "MediaScrollDown", "MediaEdit", "MediaSleep", "MediaCoffee",
+    "MediaRefresh", "MediaCalc",
+];
+
+/// Common names for keycodes, and the keycode they're short for
+const ALIASES: &[(&str, &str)] = &[
+    ("Esc", "Escape"),
+    ("Bksp", "BSpace"),
+    ("Backspace", "BSpace"),
+    ("Del", "Delete"),
+    ("Ins", "Insert"),
+    ("PgDn", "PgDown"),
+    ("Caps", "CapsLock"),
+    ("Ent", "Enter"),
+    ("Spc", "Space"),
+    ("PrtSc", "PScreen"),
+    ("PrintScreen", "PScreen"),
+    ("Semicolon", "SColon"),
+    ("Backslash", "Bslash"),
+    ("Apostrophe", "Quote"),
+    ("LCtl", "LCtrl"),
+    ("RCtl", "RCtrl"),
+    ("LSft", "LShift"),
+    ("RSft", "RShift"),
+    ("LOpt", "LAlt"),
+    ("ROpt", "RAlt"),
+    ("LCmd", "LGui"),
+    ("RCmd", "RGui"),
+    ("LWin", "LGui"),
+    ("RWin", "RGui"),
+];
+
+/// Keys on the HID consumer page, and their usage ID. They're sent by the
+/// firmware's consumer control interface rather than as keycodes.
+const CONSUMER: &[(&str, u16)] = &[
+    ("Play", 0xcd),
+    ("Next", 0xb5),
+    ("Prev", 0xb6),
+    ("Eject", 0xb8),
+    ("Mute", 0xe2),
+    ("VolUp", 0xe9),
+    ("VolDown", 0xea),
+    ("BrightUp", 0x6f),
+    ("BrightDown", 0x70),
+];
+
+/// Keys that put the host to sleep, power it off or wake it, and their
+/// System Control usage ID on the generic desktop page. They're sent by the
+/// firmware's system control interface.
+const SYSTEM: &[(&str, u8)] = &[("SysPower", 0x81), ("SysSleep", 0x82), ("SysWake", 0x83)];
+
+/// Mouse keys, and the custom action for them, which the firmware sends from
+/// its mouse interface
+const MOUSE: &[(&str, &str)] = &[
+    ("MsUp", "MouseMove(0, -1)"),
+    ("MsDown", "MouseMove(0, 1)"),
+    ("MsLeft", "MouseMove(-1, 0)"),
+    ("MsRight", "MouseMove(1, 0)"),
+    ("MsBtn1", "MouseButton(1)"),
+    ("MsBtn2", "MouseButton(2)"),
+    ("MsBtn3", "MouseButton(4)"),
+    ("MsBtn4", "MouseButton(8)"),
+    ("MsBtn5", "MouseButton(16)"),
+    ("WhUp", "MouseWheel(1, 0)"),
+    ("WhDown", "MouseWheel(-1, 0)"),
+    ("WhLeft", "MouseWheel(0, -1)"),
+    ("WhRight", "MouseWheel(0, 1)"),
+    ("MsSlow", "MousePrecision"),
+];
+
+/// The `KeyCode` variant for a keycode name or alias
+pub fn lookup(name: &str) -> Option<&'static str> {
+    KEYCODES
+        .iter()
+        .find(|&&k| k == name)
+        .or_else(|| ALIASES.iter().find(|(a, _)| *a == name).map(|(_, k)| k))
+        .copied()
+}
+
+/// The consumer page usage ID for a key name
+pub fn consumer(name: &str) -> Option<u16> {
+    CONSUMER
+        .iter()
+        .find(|(c, _)| *c == name)
+        .map(|&(_, usage)| usage)
+}
+
+/// The System Control usage ID for a key name
+pub fn system(name: &str) -> Option<u8> {
+    SYSTEM
+        .iter()
+        .find(|(s, _)| *s == name)
+        .map(|&(_, usage)| usage)
+}
+
+/// The custom action variant for a mouse key name
+pub fn mouse(name: &str) -> Option<&'static str> {
+    MOUSE
+        .iter()
+        .find(|(m, _)| *m == name)
+        .map(|&(_, action)| action)
+}
+
+/// The keycode name or alias closest to a name that isn't one, if there's one
+/// close enough that it was probably a typo
+pub fn suggest(name: &str) -> Option<&'static str> {
+    let candidates = KEYCODES
+        .iter()
+        .chain(ALIASES.iter().map(|(a, _)| a))
+        .chain(CONSUMER.iter().map(|(c, _)| c))
+        .chain(SYSTEM.iter().map(|(s, _)| s))
+        .chain(MOUSE.iter().map(|(m, _)| m));
+
+    if let Some(k) = candidates.clone().find(|k| k.eq_ignore_ascii_case(name)) {
+        return Some(k);
+    }
+
+    candidates
+        .map(|k| (distance(&k.to_lowercase(), &name.to_lowercase()), k))
+        .filter(|&(d, _)| d <= (name.len() + 1) / 3)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, k)| *k)
+}
+
+/// Levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -1,193 +1,1698 @@
 extern crate proc_macro;
-use proc_macro2::{Delimiter, Group, Literal, Punct, Spacing, TokenStream, TokenTree};
+use proc_macro2::{
+    token_stream::IntoIter, Delimiter, Group, Ident, Literal, Span, TokenStream, TokenTree,
+};
+use proc_macro_error::abort_call_site;
 use proc_macro_error::proc_macro_error;
-use proc_macro_error::{abort, emit_error};
-use quote::quote;
+use quote::{format_ident, quote, ToTokens};
+use std::cell::{Cell, RefCell};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
 
+/// `emit_error!` and `abort!`, with where the keys are written when they're
+/// from a layout file. Errors are only emitted once when the layout is
+/// expanded for more than one combination of features.
+macro_rules! emit_error {
+    ($span:expr, $($tts:tt)*) => {{
+        #[allow(unused_imports)]
+        use crate::SpanOf;
+        let diagnostic = proc_macro_error::diagnostic!($span, proc_macro_error::Level::Error, $($tts)*);
+        if crate::cfg::first_time(($span).span(), &diagnostic) {
+            crate::cfg::note(crate::file::locate(diagnostic)).emit()
+        }
+    }};
+}
+macro_rules! abort {
+    ($span:expr, $($tts:tt)*) => {
+        crate::cfg::note(crate::file::locate(proc_macro_error::diagnostic!($span, proc_macro_error::Level::Error, $($tts)*))).abort()
+    };
+}
+
+/// So the diagnostic macros can get the span of a `Span` the same way as the
+/// span of a token
+trait SpanOf {
+    fn span(&self) -> Span;
+}
+
+impl SpanOf for Span {
+    fn span(&self) -> Span {
+        *self
+    }
+}
+
+mod cfg;
+mod file;
+mod geometry;
+mod host;
+mod keycode;
+mod lint;
+mod qmk;
+mod render;
+
+use file::Piece;
+use geometry::Geometry;
+use host::{Chord, Host};
+
+/// Generates a `keyberon::layout::Layers` static from layers written out in
+/// the physical shape of the keyboard.
+///
+/// ```ignore
+/// alice_layout! {
+///     pub static LAYOUT;
+///
+///     wiring {
+///         [(3, 0) (1, 0) (0, 0) (0, 1) (0, 2) ...]
+///         ...
+///     }
+///
+///     {
+///         [Escape '`' 1 2 3 ...]
+///         ...
+///     }
+/// }
+/// ```
+///
+/// The `wiring` table has one `(row, col)` matrix position for every key,
+/// written in the same order as the keys in each layer. The matrix size and
+/// the number of layers are worked out from the table and the layers. Both
+/// the table and the layers must be the shape of the keyboard, which is the
+/// Alice layout, 5 rows of 15, 15, 14, 14 and 7 keys, unless it's given with
+/// `geometry` before them. It can be a preset, `geometry ansi60;`,
+/// `geometry ortho5x12;` or `geometry tkl;`, or the number of keys in each
+/// row, e.g. `geometry [12 12 12 12 6];`.
+///
+/// Keys are keycodes like `Escape` or `A`, the numbers `0` to `9`, and
+/// punctuation like `;` or `?`. `n` is no action and `t` is the key from the
+/// default layer. Any printable ASCII character can also be written in
+/// apostrophes, e.g. `'a'`, which is needed for the ones that can't be written
+/// on their own: `'\''`, `'"'`, `` '`' ``, `'\\'`, `'/'`, brackets and braces.
+/// Characters that are typed with Shift or AltGr, like `?` or `'A'`, hold it.
+///
+/// Characters, and strings, are typed with the keys for the keyboard layout
+/// the host is set to, which is en-US unless it's given with e.g.
+/// `host "de-DE";`. The host layouts are en-US, en-GB, de-DE and fr-FR. The
+/// numbers `0` to `9` are always the number row keys, and keycodes are always
+/// the key they name, whatever the host types with them.
+///
+/// A key can be made a tap-hold key with `tap/hold`, e.g. `Escape/LCtrl` or
+/// `Space/(1)`, and given its own timeout in ms with `tap/hold/timeout`. As
+/// `/` after a key makes it a tap-hold key, a slash keycode is written `'/'`.
+/// A punctuation key that's tapped is quoted too, like `'.'/RShift`.
+///
+/// Keys used in more than one place can be given a name with e.g.
+/// `alias Hyper = [LCtrl LAlt LShift LGui];`, and the name used like a
+/// keycode. Alias names can't be the names of keycodes.
+///
+/// Layers are switched to with `(1)` while the key is held, `(toggle 1)` until
+/// the key is pressed again, `(oneshot 1)` for the next key press only, and
+/// `(default 1)` to make it the default layer.
+///
+/// Switching to a layer that doesn't exist is an error. It's a warning when a
+/// layer can't be reached from layer 0, when a layer held with `(1)` covers
+/// the key holding it with anything but `t` or `n`, and when a toggled layer
+/// has no key to toggle it off or switch to the default layer.
+///
+/// Layers can be named by writing them as `layer nav { ... }`, and switched to
+/// by name, e.g. `(nav)`. Each named layer also gets a const with its index,
+/// e.g. `pub const NAV: usize`, and the number of layers is a const named
+/// after the layout, `LAYOUT_LAYER_COUNT`, for the firmware's `Layout` type.
+///
+/// Keys can be given a label with `@`, e.g. `Escape @ "escape"`, and named
+/// layers a description, e.g. `layer nav @ "arrows on hjkl" { ... }`. They're
+/// in statics next to the layout, named after it, for the firmware to log:
+/// `LAYOUT_LABELS` has the label of each key, or how it's written if it
+/// doesn't have one, at its matrix position in each layer, and
+/// `LAYOUT_LAYERS` has the name and description of each layer. `render`
+/// draws the labels and descriptions too. As `@` followed by a string labels
+/// the key before it, an `@` key before a string key is written `'@'`.
+///
+/// A layer can be written as the changes to an earlier layer, its parent,
+/// with `layer nav : base { H => Left, J => Down }`. The keys it doesn't
+/// change have the parent's action. Keys are given by how they're written in
+/// the parent, or by where they're written, as `(row, col)` counting from 1.
+///
+/// Keys, rows, layers and the other statements can be left out of some builds
+/// with `#[cfg(...)]` before them, using `feature = "name"`, `all()`, `any()`
+/// and `not()`, e.g. `#[cfg(feature = "mac")] LGui #[cfg(not(feature = "mac"))] LAlt`.
+/// The layout is expanded for every combination of the features it uses, at
+/// most 6 of them, so errors in any of them fail the build, with a note of
+/// the features they're in.
+///
+/// `include_keymap!("keymap.json");` adds the layers of a QMK or VIA keymap,
+/// given by its path in the crate, after the layers before it. Each layer of
+/// the keymap has every key, in the same order as they're written here.
+/// Keycodes are converted to keyberon actions, including `MO()`, `TG()`,
+/// `OSL()`, `DF()`, `LT()`, `MT()` and modifiers like `LCTL(kc)` or
+/// `LCTL_T(kc)`. Keycodes keyberon can't do, like `QK_BOOT`, are errors.
+///
+/// `include_layout!("keymap.toml");` adds the host, aliases and layers
+/// written in a TOML file, so the layout can be changed without writing any
+/// Rust:
+///
+/// ```toml
+/// host = "en-GB"
+///
+/// [alias]
+/// CapsCtrl = "Caps/LCtrl"
+///
+/// [[layer]]
+/// name = "base"
+/// keys = '''
+/// Escape '`' 1 2 3 4 5 6 7 8 9 0 - = BSpace
+/// ...
+/// '''
+///
+/// [[layer]]
+/// name = "nav"
+/// parent = "base"
+/// description = "arrows on hjkl"
+/// keys = '''
+/// H => Left, J => Down, K => Up, L => Right
+/// '''
+/// ```
+///
+/// Keys are written as they are here, with a line for each row and spaces
+/// between the keys. Layers with a parent have the keys they change, split
+/// by commas or lines. The file's layers are checked the same way, and errors
+/// in them have a note of the line and column they're at in the file.
+///
+/// `render "keymap";` draws each layer when the layout is built, as an SVG
+/// and a text file named after the layer, in the `keymap` directory of the
+/// crate. Keys are drawn as they're written in the layout. Layouts with
+/// features are drawn for each combination of them, e.g. `base+mac.svg`.
+///
+/// Keys that need firmware support, like typing a `"string"`, are emitted as
+/// `Action::Custom`. These need the firmware's custom action type, given with
+/// `custom path::to::CustomAction;`, which must have these variants:
+///
+/// ```ignore
+/// pub enum CustomAction {
+///     TypeString(&'static [&'static [KeyCode]]),
+///     Symbol(&'static [KeyCode]),
+///     DefaultLayer(usize),
+///     ToggleLayer(usize),
+///     OneShotLayer(usize),
+///     Consumer(u16),
+///     System(u8),
+///     MouseMove(i8, i8),
+///     MouseButton(u8),
+///     MouseWheel(i8, i8),
+///     MousePrecision,
+/// }
+/// ```
+///
+/// Toggled and one-shot layers are kept track of by the firmware, so once the
+/// custom action type is given, `(default 1)` goes through the firmware too.
+/// It holds them in keyberon with a `Layer` switch, so that `t` keys on them
+/// are still the default layer's key. The switches are in rows after the
+/// matrix, with the one for layer `n` in column `n`, and `LAYOUT_SWITCHES` is
+/// the first of those rows.
+/// So do characters that need Shift or AltGr, which are typed as a `Symbol`
+/// for one report, so the modifiers aren't held with the key or mixed up with
+/// the ones that really are held.
+///
+/// Media keys, `Play`, `Next`, `Prev`, `Eject`, `Mute`, `VolUp`, `VolDown`,
+/// `BrightUp` and `BrightDown`, are a `Consumer` with their usage on the HID
+/// consumer page, for the firmware to send from its consumer control
+/// interface. Without a custom action type, `Mute`, `VolUp` and `VolDown` are
+/// the keycodes instead. `SysSleep`, `SysPower` and `SysWake` are a `System`
+/// with their System Control usage, for its system control interface.
+///
+/// Mouse keys move the cursor with `MsUp`, `MsDown`, `MsLeft` and `MsRight`,
+/// press buttons with `MsBtn1` to `MsBtn5`, scroll with `WhUp`, `WhDown`,
+/// `WhLeft` and `WhRight`, and slow the cursor down while `MsSlow` is held.
+/// They're a `MouseMove`, `MouseButton`, `MouseWheel` and `MousePrecision`,
+/// for the firmware's mouse interface.
 #[proc_macro_error]
 #[proc_macro]
 pub fn alice_layout(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input: TokenStream = input.into();
+    cfg::reset();
+    let features = cfg::features(&input);
 
     let mut out = TokenStream::new();
+    for enabled in cfg::combinations(&features) {
+        cfg::enable(&enabled);
+        out.extend(expand(input.clone(), cfg::attribute(&features)));
+    }
+    out.into()
+}
 
-    let mut inside = TokenStream::new();
+/// Expands the layout for the features being expanded with, each item behind
+/// their `#[cfg(...)]`
+fn expand(input: TokenStream, cfg: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter().peekable();
 
-    for t in input {
+    let (vis, name) = parse_static(&mut tokens);
+
+    let mut context = Context::default();
+    let mut wiring = None;
+    let mut host = None;
+    let mut render = None;
+    let mut included = Vec::new();
+    let mut layers = Vec::new();
+    let mut descriptions = Vec::new();
+
+    loop {
+        if !cfg::is_enabled(&mut tokens) {
+            skip_statement(&mut tokens);
+            continue;
+        }
+        let t = match tokens.next() {
+            Some(t) => t,
+            None => break,
+        };
         match t {
+            TokenTree::Ident(i) if i == "wiring" => match tokens.next() {
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => {
+                    if wiring.is_some() {
+                        emit_error!(i, "Wiring table is defined more than once");
+                    }
+                    wiring = Some(parse_wiring(&context.geometry, &g));
+                }
+                _ => abort!(i, "Expected wiring table: wiring { ... }"),
+            },
+            TokenTree::Ident(i) if i == "geometry" => {
+                if wiring.is_some() || !layers.is_empty() {
+                    emit_error!(
+                        i,
+                        "The geometry has to come before the wiring table and layers"
+                    );
+                }
+                context.geometry = parse_geometry(&i, &mut tokens);
+            }
+            TokenTree::Ident(i) if i == "custom" => {
+                if context.custom.is_some() {
+                    emit_error!(i, "Custom action type is defined more than once");
+                }
+                context.custom = Some(parse_custom(&i, &mut tokens));
+            }
+            TokenTree::Ident(i) if i == "host" => {
+                if host.is_some() {
+                    emit_error!(i, "Host layout is defined more than once");
+                }
+                host = Some(parse_host(&i, &mut tokens));
+            }
+            TokenTree::Ident(i) if i == "render" => {
+                if render.is_some() {
+                    emit_error!(i, "Render directory is defined more than once");
+                }
+                render = Some(parse_render(&i, &mut tokens));
+            }
+            TokenTree::Ident(i) if i == "include_keymap" => {
+                let (span, path) = parse_include(&i, &mut tokens, "keymap.json");
+                for g in qmk::read_keymap(&path, span, &context.geometry.shape()) {
+                    context.layer_names.push(None);
+                    descriptions.push(None);
+                    layers.push((span, None, Body::Written(g)));
+                }
+                included.push(path);
+            }
+            TokenTree::Ident(i) if i == "include_layout" => {
+                let (span, path) = parse_include(&i, &mut tokens, "keymap.toml");
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let layout = file::read_layout(&path, &name, span);
+
+                if let Some(piece) = layout.host {
+                    file::at(piece.location);
+                    if host.is_some() {
+                        emit_error!(span, "Host layout is defined more than once");
+                    }
+                    host = Some(parse_host(&i, &mut piece.tokens.into_iter().peekable()));
+                }
+                for (name, piece) in layout.aliases {
+                    file::at(piece.location);
+                    let name = file_name(&name, span);
+                    let key = piece.tokens;
+                    let alias =
+                        parse_alias(&i, &mut quote! { #name = #key; }.into_iter().peekable());
+                    add_alias(&mut context, alias);
+                }
+                for layer in layout.layers {
+                    let name = layer.name.map(|(name, location)| {
+                        file::at(Some(location));
+                        file_name(&name, span)
+                    });
+                    let parent = layer.parent.map(|(parent, location)| {
+                        file::at(Some(location));
+                        file_name(&parent, span)
+                    });
+                    file::at(Some(layer.location.clone()));
+                    if let Some(name) = name
+                        .as_ref()
+                        .filter(|_| context.layer_names.contains(&name))
+                    {
+                        emit_error!(span, "Layer `{}` is defined more than once", name);
+                    }
+                    context.layer_names.push(name);
+                    descriptions.push(layer.description);
+                    layers.push((span, parent, Body::File(layer.rows, layer.location)));
+                }
+                file::at(None);
+                included.push(path);
+            }
+            TokenTree::Ident(i) if i == "alias" => {
+                let alias = parse_alias(&i, &mut tokens);
+                add_alias(&mut context, alias);
+            }
+            TokenTree::Ident(i) if i == "layer" => {
+                let name = match tokens.next() {
+                    Some(TokenTree::Ident(name)) => name,
+                    _ => abort!(i, "Expected a layer name: layer NAME { ... }"),
+                };
+                let parent = match tokens.peek() {
+                    Some(TokenTree::Punct(p)) if p.as_char() == ':' => {
+                        tokens.next();
+                        match tokens.next() {
+                            Some(TokenTree::Ident(parent)) => Some(parent),
+                            _ => abort!(
+                                name,
+                                "Expected a parent layer: layer {} : PARENT {{ ... }}",
+                                name
+                            ),
+                        }
+                    }
+                    _ => None,
+                };
+                let description = parse_caption(&mut tokens);
+                match tokens.next() {
+                    Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => {
+                        if context.layer_names.contains(&Some(name.clone())) {
+                            emit_error!(name, "Layer `{}` is defined more than once", name);
+                        }
+                        layers.push((name.span(), parent, Body::Written(g)));
+                        context.layer_names.push(Some(name));
+                        descriptions.push(description);
+                    }
+                    _ => abort!(name, "Expected layer: layer {} {{ ... }}", name),
+                }
+            }
             TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => {
-                let layer = parse_layer(g.stream());
-                inside.extend(quote! {
-                    [#layer],
-                });
+                context.layer_names.push(None);
+                descriptions.push(None);
+                layers.push((g.span_open(), None, Body::Written(g)));
             }
-            _ => abort!(t, "Invalid token, expected layer: {{ ... }}"),
+            _ => abort!(t, "Invalid token, expected layer: { ... }"),
         }
     }
 
-    let all: TokenStream = quote! { [#inside] };
-    out.extend(all);
-    out.into()
+    let wiring = match wiring {
+        Some(w) => w,
+        None => abort_call_site!("Missing wiring table: wiring { ... }"),
+    };
+    context.host = host.unwrap_or_default();
+
+    let mut spans = Vec::new();
+    let mut parsed: Vec<Vec<Key>> = Vec::new();
+    for (index, (span, parent, body)) in layers.into_iter().enumerate() {
+        context.position.set((index, 0));
+        spans.push(span);
+        let keys = match (parent, body) {
+            (Some(parent), body) => {
+                let changes = match body {
+                    Body::Written(g) => vec![Piece {
+                        tokens: g.stream(),
+                        location: None,
+                    }],
+                    Body::File(mut rows, location) => {
+                        file::at(Some(location));
+                        rows.pop().unwrap_or_default()
+                    }
+                };
+                match context.layer_names[..index]
+                    .iter()
+                    .position(|name| name.as_ref() == Some(&parent))
+                {
+                    Some(parent) => {
+                        parse_override_layer(&context, changes, parent, &parsed[parent])
+                    }
+                    None => {
+                        abort!(parent, "There is no layer named `{}` before this one", parent; help = "A layer's parent has to come before it")
+                    }
+                }
+            }
+            (None, Body::Written(g)) => parse_layer(&context, &g),
+            (None, Body::File(rows, location)) => {
+                let rows = rows.into_iter().map(|row| (span, row)).collect();
+                parse_rows(&context, span, rows, Some(location))
+            }
+        };
+        file::at(None);
+        parsed.push(keys);
+    }
+    let layers = parsed;
+    let warnings = lint::lint(&context, &layers, &spans);
+
+    let names: Vec<String> = context
+        .layer_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| match name {
+            Some(name) => name.to_string(),
+            None => format!("layer{}", index),
+        })
+        .collect();
+
+    if let Some((span, dir)) = render {
+        let drawings: Vec<render::Drawing> = layers
+            .iter()
+            .enumerate()
+            .map(|(index, keys)| render::Drawing {
+                name: format!("{}{}", names[index], cfg::suffix()),
+                description: descriptions[index].clone(),
+                keys: keys.iter().map(|k| k.caption().to_string()).collect(),
+            })
+            .collect();
+        if let Err(e) = render::render(&dir, &context.geometry, &drawings) {
+            emit_error!(span, "Couldn't draw the layers in {}: {}", dir.display(), e);
+        }
+    }
+
+    let labels: Vec<TokenStream> = layers
+        .iter()
+        .map(|keys| {
+            let labels = keys.iter().map(|k| k.caption().into_token_stream());
+            arrange(&wiring, labels, quote! { "" }, &[])
+        })
+        .collect();
+    let layer_info = names.iter().zip(&descriptions).map(|(name, description)| {
+        let description = description.as_deref().unwrap_or_default();
+        quote! { (#name, #description) }
+    });
+
+    // The firmware holds toggled and one-shot layers with these, so keyberon
+    // keeps the real default layer for their `t` keys to fall through to
+    let switch_rows = match context.custom {
+        Some(_) => layers.len().div_ceil(wiring.cols.max(1)),
+        None => 0,
+    };
+    let switches: Vec<TokenStream> = (0..switch_rows)
+        .map(|row| {
+            let switches = (0..wiring.cols).map(|col| match row * wiring.cols + col {
+                layer if layer < layers.len() => quote! { keyberon::action::Action::Layer(#layer) },
+                _ => quote! { keyberon::action::Action::NoOp },
+            });
+            quote! { [#(#switches),*] }
+        })
+        .collect();
+
+    let count = Literal::usize_unsuffixed(layers.len());
+    let layers: Vec<TokenStream> = layers
+        .into_iter()
+        .map(|keys| {
+            let actions = keys.into_iter().map(|k| k.action);
+            let noop = quote! { keyberon::action::Action::NoOp };
+            arrange(&wiring, actions, noop, &switches)
+        })
+        .collect();
+
+    let cols = Literal::usize_unsuffixed(wiring.cols);
+    let rows = Literal::usize_unsuffixed(wiring.rows);
+    let all_rows = Literal::usize_unsuffixed(wiring.rows + switch_rows);
+
+    let custom = context.custom.iter();
+
+    let consts = context
+        .layer_names
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let name = name.as_ref()?;
+            let name = Ident::new(&name.to_string().to_uppercase(), name.span());
+            Some(quote! {
+                #cfg
+                #[allow(dead_code)]
+                #vis const #name: usize = #index;
+            })
+        });
+
+    // Including the files makes cargo build the layout again when they change
+    let included = included.iter().map(|path| path.to_string_lossy());
+
+    let warnings = match cfg.is_empty() || warnings.is_empty() {
+        true => warnings,
+        false => quote! { #cfg const _: () = { #warnings }; },
+    };
+
+    let labels_name = format_ident!("{}_LABELS", name);
+    let layers_name = format_ident!("{}_LAYERS", name);
+    let count_name = format_ident!("{}_LAYER_COUNT", name);
+    let switches_name = format_ident!("{}_SWITCHES", name);
+    let switches = context.custom.as_ref().map(|_| {
+        quote! {
+            #cfg
+            #[allow(dead_code)]
+            #vis const #switches_name: usize = #rows;
+        }
+    });
+
+    quote! {
+        #cfg
+        #vis static #name: keyberon::layout::Layers<#cols, #all_rows, #count #(, #custom)*> = [#(#layers),*];
+        #cfg
+        #[allow(dead_code)]
+        #vis static #labels_name: [[[&str; #cols]; #rows]; #count] = [#(#labels),*];
+        #cfg
+        #[allow(dead_code)]
+        #vis static #layers_name: [(&str, &str); #count] = [#(#layer_info),*];
+        #cfg
+        #[allow(dead_code)]
+        #vis const #count_name: usize = #count;
+        #switches
+        #(#consts)*
+        #(#cfg const _: &[u8] = include_bytes!(#included);)*
+        #warnings
+    }
 }
 
-fn parse_layer(input: TokenStream) -> TokenStream {
-    let mut out = TokenStream::new();
-    for t in input {
+/// Skips a statement that isn't in the expansion being parsed. Layers and the
+/// wiring table end with their `{ ... }`, and everything else with a `;`, even
+/// if there's a `{ ... }` before it, like in `alias X = { ... };`.
+fn skip_statement(tokens: &mut Peekable<IntoIter>) {
+    let block = match tokens.peek() {
+        Some(TokenTree::Ident(i)) => i == "wiring" || i == "layer",
+        Some(TokenTree::Group(g)) => g.delimiter() == Delimiter::Brace,
+        _ => false,
+    };
+    for t in tokens.by_ref() {
+        match t {
+            TokenTree::Punct(p) if p.as_char() == ';' && !block => break,
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Brace && block => break,
+            _ => (),
+        }
+    }
+}
+
+/// Parses the `pub static NAME;` declaration at the start of the layout
+fn parse_static(tokens: &mut Peekable<IntoIter>) -> (TokenStream, Ident) {
+    let mut vis = TokenStream::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(i)) if i == "static" => break,
+            Some(TokenTree::Ident(i)) if i == "pub" && vis.is_empty() => vis.extend(quote! { #i }),
+            Some(TokenTree::Group(g))
+                if g.delimiter() == Delimiter::Parenthesis && !vis.is_empty() =>
+            {
+                vis.extend(quote! { #g })
+            }
+            Some(t) => abort!(t, "Expected layout declaration: pub static NAME;"),
+            None => abort_call_site!("Expected layout declaration: pub static NAME;"),
+        }
+    }
+
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(i)) => i,
+        Some(t) => abort!(t, "Expected a name for the layout"),
+        None => abort_call_site!("Expected a name for the layout"),
+    };
+
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ';' => (),
+        Some(t) => abort!(t, "Expected `;` after the layout name"),
+        None => abort_call_site!("Expected `;` after the layout name"),
+    }
+
+    (vis, name)
+}
+
+/// Things declared in the layout that keys need to know about while parsing
+#[derive(Default)]
+struct Context {
+    /// Path to the firmware's custom action type
+    custom: Option<TokenStream>,
+    /// The shape of the keyboard
+    geometry: Geometry,
+    /// The keyboard layout the host is set to
+    host: Host,
+    /// Names given to keys with `alias`, and the key as written
+    aliases: Vec<(Ident, TokenStream)>,
+    /// The aliases being expanded, to catch ones that use themselves
+    expanding: RefCell<Vec<Ident>>,
+    /// Names of the layers, in order; `None` for layers without a name
+    layer_names: Vec<Option<Ident>>,
+    /// Layer and key index of the key being parsed
+    position: Cell<(usize, usize)>,
+    /// Every layer switch to a layer number, for linting the layout
+    switches: RefCell<Vec<lint::LayerSwitch>>,
+    /// Whether a key's action can't be linted, like an `{ expression }`,
+    /// so it could switch to any layer
+    opaque: Cell<bool>,
+}
+
+impl Context {
+    /// Resolves a layer name in a layer switch to the index of the layer.
+    /// Anything else, like a layer number, is left as it is.
+    fn layer_index(&self, layer: TokenStream) -> TokenStream {
+        let mut tokens = layer.clone().into_iter();
+        match (tokens.next(), tokens.next()) {
+            (Some(TokenTree::Ident(i)), None) => {
+                match self
+                    .layer_names
+                    .iter()
+                    .position(|name| name.as_ref() == Some(&i))
+                {
+                    Some(index) => Literal::usize_unsuffixed(index).into_token_stream(),
+                    None => {
+                        let names: Vec<String> = self
+                            .layer_names
+                            .iter()
+                            .flatten()
+                            .map(|n| format!("`{}`", n))
+                            .collect();
+                        let help = match names.is_empty() {
+                            true => "Layers are named with `layer NAME { ... }`".to_string(),
+                            false => format!("The named layers are {}", names.join(", ")),
+                        };
+                        emit_error!(i, "There is no layer named `{}`", i; help = help);
+                        quote! { 0 }
+                    }
+                }
+            }
+            _ => layer,
+        }
+    }
+
+    /// The layer's name for messages, or its number if it has no name
+    fn layer_name(&self, layer: usize) -> String {
+        match self.layer_names.get(layer) {
+            Some(Some(name)) => format!("`{}`", name),
+            _ => layer.to_string(),
+        }
+    }
+
+    /// `Action::Custom` for a variant of the custom action type
+    fn custom_action(&self, span: Span, action: TokenStream) -> TokenStream {
+        match &self.custom {
+            Some(custom) => quote! { keyberon::action::Action::Custom(#custom::#action) },
+            None => {
+                emit_error!(span, "This key needs a custom action type"; help = "Add the firmware's custom action type to the layout: custom path::to::CustomAction;");
+                quote! { keyberon::action::Action::NoOp }
+            }
+        }
+    }
+}
+
+/// Parses the path after `custom`, up to the closing `;`
+fn parse_custom(keyword: &Ident, tokens: &mut Peekable<IntoIter>) -> TokenStream {
+    let mut path = TokenStream::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ';' => break,
+            Some(t) => path.extend(Some(t)),
+            None => abort!(keyword, "Expected `;` after the custom action type"),
+        }
+    }
+    if path.is_empty() {
+        abort!(
+            keyword,
+            "Expected custom action type: custom path::to::CustomAction;"
+        );
+    }
+    path
+}
+
+/// Parses the directory after `render`, and the closing `;`. The directory is
+/// relative to the crate being built.
+fn parse_render(keyword: &Ident, tokens: &mut Peekable<IntoIter>) -> (Span, PathBuf) {
+    let dir = match tokens.next() {
+        Some(TokenTree::Literal(l)) => match string_value(&l.to_string()) {
+            Some(dir) => (l.span(), PathBuf::from(dir)),
+            None => abort!(l, "Expected a directory: render \"keymap\";"),
+        },
+        _ => abort!(keyword, "Expected a directory: render \"keymap\";"),
+    };
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ';' => (),
+        _ => abort!(keyword, "Expected `;` after the render directory"),
+    }
+    match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(root) => (dir.0, Path::new(&root).join(dir.1)),
+        None => dir,
+    }
+}
+
+/// Parses `("file");` after `include_keymap!` or `include_layout!`, and gives
+/// the path in the crate
+fn parse_include(
+    keyword: &Ident,
+    tokens: &mut Peekable<IntoIter>,
+    example: &str,
+) -> (Span, PathBuf) {
+    let expected = format!("Expected a file: {}!(\"{}\");", keyword, example);
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '!' => (),
+        _ => abort!(keyword, "{}", expected),
+    }
+    let path = match tokens.next() {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => {
+            let mut inner = g.stream().into_iter();
+            match (inner.next(), inner.next()) {
+                (Some(TokenTree::Literal(l)), None) => match string_value(&l.to_string()) {
+                    Some(path) => (l.span(), PathBuf::from(path)),
+                    None => abort!(l, "{}", expected),
+                },
+                _ => abort!(g, "{}", expected),
+            }
+        }
+        _ => abort!(keyword, "{}", expected),
+    };
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ';' => (),
+        _ => abort!(keyword, "Expected `;` after `{}!(...)`", keyword),
+    }
+    match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(root) => (path.0, Path::new(&root).join(path.1)),
+        None => path,
+    }
+}
+
+/// Parses `NAME = key;` after `alias`
+fn parse_alias(keyword: &Ident, tokens: &mut Peekable<IntoIter>) -> (Ident, TokenStream) {
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(name)) => name,
+        _ => abort!(keyword, "Expected an alias name: alias NAME = key;"),
+    };
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == '=' => (),
+        _ => abort!(
+            name,
+            "Expected `=` after the alias name: alias {} = key;",
+            name
+        ),
+    }
+
+    let mut key = TokenStream::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == ';' => break,
+            Some(t) => key.extend(Some(t)),
+            None => abort!(name, "Expected `;` after the key for alias `{}`", name),
+        }
+    }
+    if key.is_empty() {
+        abort!(name, "Expected a key for alias `{}`", name);
+    }
+
+    let text = name.to_string();
+    if let Some(k) = keycode::lookup(&text) {
+        emit_error!(name, "Alias `{}` has the same name as keycode `{}`", name, k; help = "Aliases need names that aren't keycodes");
+    } else if ["n", "t", "_"].contains(&text.as_str()) {
+        emit_error!(name, "`{}` can't be used as an alias name", name);
+    }
+    (name, key)
+}
+
+/// Adds an alias to the layout, unless there's already one with its name
+fn add_alias(context: &mut Context, (name, key): (Ident, TokenStream)) {
+    if context.aliases.iter().any(|(n, _)| *n == name) {
+        emit_error!(name, "Alias `{}` is defined more than once", name);
+    }
+    context.aliases.push((name, key));
+}
+
+/// An alias or layer name from a layout file, which has to be a name that
+/// could be written in the layout
+fn file_name(name: &str, span: Span) -> Ident {
+    let mut tokens = name.parse::<TokenStream>().into_iter().flatten();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(i)), None) => Ident::new(&i.to_string(), span),
+        _ => {
+            abort!(span, "`{}` isn't a name", name; help = "Names are letters, numbers and `_`, starting with a letter")
+        }
+    }
+}
+
+/// Parses the name of the host layout after `host`, and the closing `;`
+fn parse_host(keyword: &Ident, tokens: &mut Peekable<IntoIter>) -> Host {
+    let host = match tokens.next() {
+        Some(TokenTree::Literal(l)) => {
+            match string_value(&l.to_string()).and_then(|name| Host::from_name(&name)) {
+                Some(host) => host,
+                None => {
+                    abort!(l, "Unknown host layout {}", l; help = "The host layouts are {}", Host::names())
+                }
+            }
+        }
+        _ => abort!(keyword, "Expected host layout: host \"en-US\";"),
+    };
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ';' => (),
+        _ => abort!(keyword, "Expected `;` after the host layout"),
+    }
+    host
+}
+
+/// Parses a preset name, like `ansi60`, or the number of keys in each row,
+/// like `[12 12 12 12 12]`, after `geometry`
+fn parse_geometry(keyword: &Ident, tokens: &mut Peekable<IntoIter>) -> Geometry {
+    let geometry = match tokens.next() {
+        Some(TokenTree::Ident(name)) => match Geometry::preset(&name.to_string()) {
+            Some(geometry) => geometry,
+            None => {
+                abort!(name, "Unknown geometry `{}`", name; help = "The geometries are {}, or the number of keys in each row: geometry [12 12 12 12 12];", Geometry::presets())
+            }
+        },
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+            let rows: Vec<usize> = g
+                .stream()
+                .into_iter()
+                .filter(|t| !matches!(t, TokenTree::Punct(p) if p.as_char() == ','))
+                .map(|t| match t.to_string().parse() {
+                    Ok(len) if len > 0 => len,
+                    _ => abort!(t, "Expected the number of keys in the row"),
+                })
+                .collect();
+            if rows.is_empty() {
+                abort!(
+                    g,
+                    "Expected the number of keys in each row: geometry [12 12 12 12 12];"
+                );
+            }
+            Geometry::rows(&rows)
+        }
+        _ => abort!(keyword, "Expected a geometry: geometry ansi60;"),
+    };
+    match tokens.next() {
+        Some(TokenTree::Punct(p)) if p.as_char() == ';' => (),
+        _ => abort!(keyword, "Expected `;` after the geometry"),
+    }
+    geometry
+}
+
+/// Where each key of a layer is wired in the switch matrix
+struct Wiring {
+    /// `(row, col)` of each key, in the order the keys are written
+    positions: Vec<(usize, usize)>,
+    rows: usize,
+    cols: usize,
+}
+
+fn parse_wiring(geometry: &Geometry, wiring: &Group) -> Wiring {
+    let mut positions: Vec<(usize, usize)> = Vec::new();
+    let mut rows = Vec::new();
+    for t in wiring.stream() {
         match t {
             TokenTree::Group(g) if g.delimiter() == Delimiter::Bracket => {
-                let row = parse_row(g.stream());
-                out.extend(quote! {
-                    #row
-                });
+                let start = positions.len();
+                for t in g.stream() {
+                    let position = parse_position(&t);
+                    if positions.contains(&position) {
+                        emit_error!(
+                            t,
+                            "Matrix position {:?} is wired to more than one key",
+                            position
+                        );
+                    }
+                    positions.push(position);
+                }
+                rows.push((g.span(), positions.len() - start));
             }
             TokenTree::Punct(p) if p.as_char() == ',' => (),
             _ => abort!(t, "Invalid token, expected row: [ ... ]"),
         }
     }
-    out
+    check_shape(geometry, wiring.span(), &rows);
+
+    Wiring {
+        rows: positions.iter().map(|&(r, _)| r + 1).max().unwrap_or(0),
+        cols: positions.iter().map(|&(_, c)| c + 1).max().unwrap_or(0),
+        positions,
+    }
 }
 
-fn parse_row(input: TokenStream) -> TokenStream {
-    let mut out = TokenStream::new();
-    for t in input {
+fn parse_position(t: &TokenTree) -> (usize, usize) {
+    let numbers: Vec<usize> = match t {
+        TokenTree::Group(g) if g.delimiter() == Delimiter::Parenthesis => g
+            .stream()
+            .into_iter()
+            .filter(|t| !matches!(t, TokenTree::Punct(p) if p.as_char() == ','))
+            .map(|t| match &t {
+                TokenTree::Literal(l) => match l.to_string().parse() {
+                    Ok(n) => n,
+                    Err(_) => abort!(t, "Expected a matrix row or column number"),
+                },
+                _ => abort!(t, "Expected a matrix row or column number"),
+            })
+            .collect(),
+        _ => abort!(t, "Invalid token, expected matrix position: (row, col)"),
+    };
+
+    match numbers[..] {
+        [row, col] => (row, col),
+        _ => abort!(t, "Expected matrix position: (row, col)"),
+    }
+}
+
+/// Moves the actions or labels of a layer's keys to where they are wired in
+/// the matrix. Matrix positions that aren't wired to a key are filled with
+/// `unwired`, and the rows in `below` go after the matrix rows.
+fn arrange(
+    wiring: &Wiring,
+    keys: impl Iterator<Item = TokenStream>,
+    unwired: TokenStream,
+    below: &[TokenStream],
+) -> TokenStream {
+    let mut matrix = vec![vec![unwired; wiring.cols]; wiring.rows];
+    for (&(row, col), key) in wiring.positions.iter().zip(keys) {
+        matrix[row][col] = key;
+    }
+
+    let rows = matrix
+        .into_iter()
+        .map(|row| quote! { [#(#row),*] })
+        .chain(below.iter().cloned());
+    quote! { [#(#rows),*] }
+}
+
+/// The keys of a layer, as written in the layout or in a layout file
+enum Body {
+    Written(Group),
+    /// The rows of keys, and where the layer is written
+    File(Vec<Vec<Piece>>, String),
+}
+
+/// A key of a layer
+#[derive(Clone)]
+struct Key {
+    action: TokenStream,
+    /// The key as written, without its hold action if it's a tap-hold key.
+    /// Char literals are written without apostrophes.
+    label: String,
+    /// The whole key as written, for drawing the layer
+    written: String,
+    span: Span,
+    /// Where the key is written, if it's from a layout file
+    location: Option<String>,
+    /// The label given with `@ "label"`, if it has one
+    caption: Option<String>,
+}
+
+impl Key {
+    /// What the key is called in the layout's labels and drawings: its label
+    /// if it has one, or else how it's written
+    fn caption(&self) -> &str {
+        self.caption.as_deref().unwrap_or(&self.written)
+    }
+}
+
+/// A key's label from its first token
+fn label(t: &TokenTree) -> String {
+    match t {
+        TokenTree::Literal(l) if l.to_string().starts_with('\'') => {
+            match char_value(&l.to_string()) {
+                Some(c) => c.to_string(),
+                None => l.to_string(),
+            }
+        }
+        _ => t.to_string(),
+    }
+}
+
+fn parse_layer(context: &Context, layer: &Group) -> Vec<Key> {
+    let mut rows = Vec::new();
+    let mut tokens = layer.stream().into_iter().peekable();
+    loop {
+        let enabled = cfg::is_enabled(&mut tokens);
+        let t = match tokens.next() {
+            Some(t) => t,
+            None => break,
+        };
         match t {
-            TokenTree::Ident(i) => match i.to_string().as_str() {
-                "n" => out.extend(quote! { keyberon::action::Action::NoOp, }),
-                "t" => out.extend(quote! { keyberon::action::Action::Trans, }),
-                _ => out.extend(quote! {
-                    keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::#i),
-                }),
-            },
-            TokenTree::Punct(p) => punctuation_to_keycode(&p, &mut out),
-            TokenTree::Literal(l) => literal_to_keycode(&l, &mut out),
-            TokenTree::Group(g) => parse_group(&g, &mut out),
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Bracket && !enabled => (),
+            TokenTree::Group(g) if g.delimiter() == Delimiter::Bracket => {
+                let keys = Piece {
+                    tokens: g.stream(),
+                    location: None,
+                };
+                rows.push((g.span(), vec![keys]));
+            }
+            TokenTree::Punct(p) if p.as_char() == ',' => (),
+            _ => abort!(t, "Invalid token, expected row: [ ... ]"),
+        }
+    }
+    parse_rows(context, layer.span(), rows, None)
+}
+
+/// Parses the rows of a layer, given in pieces so keys from a layout file
+/// can each have where they're written
+fn parse_rows(
+    context: &Context,
+    span: Span,
+    rows: Vec<(Span, Vec<Piece>)>,
+    location: Option<String>,
+) -> Vec<Key> {
+    let mut out = Vec::new();
+    let count = rows.len();
+    for (i, (row, pieces)) in rows.into_iter().enumerate() {
+        let start = out.len();
+        let row_location = pieces.first().and_then(|p| p.location.clone());
+        for piece in pieces {
+            file::at(piece.location);
+            parse_row(context, piece.tokens, &mut out);
         }
+        file::at(row_location);
+        check_row(&context.geometry, row, i, out.len() - start);
     }
+    file::at(location);
+    check_rows(&context.geometry, span, count);
     out
 }
 
-fn parse_group(g: &Group, out: &mut TokenStream) {
+/// Parses a layer that only has the keys it changes from its parent layer,
+/// e.g. `H => Left, J => Down`. The rest of its keys are the parent's.
+fn parse_override_layer(
+    context: &Context,
+    changes: Vec<Piece>,
+    parent_index: usize,
+    parent: &[Key],
+) -> Vec<Key> {
+    let (index, _) = context.position.get();
+    let mut keys = parent.to_vec();
+    let mut overridden = vec![false; keys.len()];
+
+    for piece in changes {
+        if piece.location.is_some() {
+            file::at(piece.location);
+        }
+        let mut tokens = piece.tokens.into_iter().peekable();
+        loop {
+            if !cfg::is_enabled(&mut tokens) {
+                for t in tokens.by_ref() {
+                    if matches!(t, TokenTree::Punct(ref p) if p.as_char() == ',') {
+                        break;
+                    }
+                }
+                continue;
+            }
+            let t = match tokens.next() {
+                Some(t) => t,
+                None => break,
+            };
+            let position = override_position(context, &t, parent_index, parent);
+            match (tokens.next(), tokens.next()) {
+                (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b)))
+                    if a.as_char() == '=' && b.as_char() == '>' => {}
+                _ => abort!(t, "Expected `=>` after the key to change: {} => key", t),
+            }
+            let key = match tokens.next() {
+                Some(key) => key,
+                None => abort!(t, "Expected the key to change it to: {} => key", t),
+            };
+
+            context.position.set((index, position.unwrap_or(0)));
+            let key = parse_layer_key(context, key, &mut tokens);
+            match tokens.next() {
+                Some(TokenTree::Punct(p)) if p.as_char() == ',' => (),
+                Some(t) => emit_error!(t, "Expected `,` after the key"),
+                None => (),
+            }
+
+            if let Some(position) = position {
+                if overridden[position] {
+                    emit_error!(t, "This key is changed more than once");
+                }
+                overridden[position] = true;
+                keys[position] = key;
+            }
+        }
+    }
+
+    // The keys from the parent switch layers on this layer too
+    let mut switches = context.switches.borrow_mut();
+    let inherited: Vec<lint::LayerSwitch> = switches
+        .iter()
+        .filter(|s| s.layer == parent_index && !overridden[s.key])
+        .map(|s| lint::LayerSwitch {
+            layer: index,
+            location: s.location.clone(),
+            ..*s
+        })
+        .collect();
+    switches.extend(inherited);
+
+    keys
+}
+
+/// Finds the key an override layer changes, either by its label in the
+/// parent layer or by where it's written, as `(row, col)` counting from 1
+fn override_position(
+    context: &Context,
+    t: &TokenTree,
+    parent_index: usize,
+    parent: &[Key],
+) -> Option<usize> {
+    if let TokenTree::Group(g) = t {
+        let numbers: Vec<usize> = g
+            .stream()
+            .into_iter()
+            .filter(|t| !matches!(t, TokenTree::Punct(p) if p.as_char() == ','))
+            .map_while(|t| t.to_string().parse().ok())
+            .collect();
+        if let (Delimiter::Parenthesis, &[row, col]) = (g.delimiter(), &numbers[..]) {
+            let shape = context.geometry.shape();
+            let position = shape
+                .get(row.wrapping_sub(1))
+                .filter(|&&len| (1..=len).contains(&col))
+                .map(|_| shape[..row - 1].iter().sum::<usize>() + col - 1);
+            match position {
+                None => emit_error!(t, "There is no key at row {}, column {}", row, col),
+                // The parent's rows are short when they've already been errors
+                Some(position) if position >= parent.len() => {
+                    emit_error!(t, "Layer {} doesn't have a key at row {}, column {}", context.layer_name(parent_index), row, col; help = "Its rows need to be the shape of the keyboard first")
+                }
+                Some(_) => (),
+            }
+            return position.filter(|&position| position < parent.len());
+        }
+    }
+
+    let label = label(t);
+    let mut found = parent
+        .iter()
+        .enumerate()
+        .filter(|(_, key)| key.label == label)
+        .map(|(i, _)| i);
+    match (found.next(), found.next()) {
+        (Some(position), None) => return Some(position),
+        (Some(_), Some(_)) => {
+            emit_error!(t, "There is more than one `{}` key in layer {}", label, context.layer_name(parent_index); help = "Give the key by where it's written instead: (row, col)")
+        }
+        (None, _) => {
+            emit_error!(t, "There is no `{}` key in layer {}", label, context.layer_name(parent_index); help = "Keys can also be given by where they're written: (row, col)")
+        }
+    }
+    None
+}
+
+/// Checks that a layer or wiring table is the shape of the keyboard, given
+/// the span and length of each of its rows
+fn check_shape(geometry: &Geometry, span: Span, rows: &[(Span, usize)]) {
+    for (i, &(row, len)) in rows.iter().enumerate() {
+        check_row(geometry, row, i, len);
+    }
+    check_rows(geometry, span, rows.len());
+}
+
+/// Checks that row `i` of a layer or wiring table has the keys it should
+fn check_row(geometry: &Geometry, row: Span, i: usize, len: usize) {
+    let shape = geometry.shape();
+    match shape.get(i) {
+        Some(&expected) if expected != len => {
+            emit_error!(
+                row,
+                "Expected {} keys in row {}, found {}",
+                expected,
+                i + 1,
+                len
+            )
+        }
+        Some(_) => (),
+        None => emit_error!(
+            row,
+            "Unexpected row, {} has {} rows",
+            geometry.name,
+            shape.len()
+        ),
+    }
+}
+
+/// Checks that a layer or wiring table has all of its rows
+fn check_rows(geometry: &Geometry, span: Span, rows: usize) {
+    let expected = geometry.shape().len();
+    if rows < expected {
+        emit_error!(span, "Expected {} rows, found {}", expected, rows);
+    }
+}
+
+/// Parses the keys of a row onto the end of the layer's keys
+fn parse_row(context: &Context, input: TokenStream, out: &mut Vec<Key>) {
+    let mut tokens = input.into_iter().peekable();
+    loop {
+        let enabled = cfg::is_enabled(&mut tokens);
+        let t = match tokens.next() {
+            Some(t) => t,
+            None => break,
+        };
+        if !enabled {
+            skip_key(&mut tokens);
+            continue;
+        }
+        let (layer, _) = context.position.get();
+        context.position.set((layer, out.len()));
+
+        out.push(parse_layer_key(context, t, &mut tokens));
+    }
+}
+
+/// Skips the hold action and timeout of a key that isn't in the expansion
+/// being parsed, after its first token
+fn skip_key(tokens: &mut Peekable<IntoIter>) {
+    while let Some(TokenTree::Punct(p)) = tokens.peek() {
+        if p.as_char() != '/' {
+            break;
+        }
+        tokens.next();
+        tokens.next();
+    }
+    parse_caption(tokens);
+}
+
+/// Parses the `@ "label"` after a key or a layer's name, if it has one. An
+/// `@` that isn't followed by a string is the `@` key.
+fn parse_caption(tokens: &mut Peekable<IntoIter>) -> Option<String> {
+    let mut ahead = tokens.clone();
+    let caption = match (ahead.next(), ahead.next()) {
+        (Some(TokenTree::Punct(p)), Some(TokenTree::Literal(l))) if p.as_char() == '@' => {
+            string_value(&l.to_string())?
+        }
+        _ => return None,
+    };
+    tokens.next();
+    tokens.next();
+    Some(caption)
+}
+
+/// Parses a key of a layer, along with how it's written
+fn parse_layer_key(context: &Context, t: TokenTree, tokens: &mut Peekable<IntoIter>) -> Key {
+    let rest: Vec<TokenTree> = tokens.clone().collect();
+    let label = label(&t);
+    let span = t.span();
+    let mut written = label.clone();
+
+    let action = parse_action(context, t, tokens);
+    let used = rest.len() - tokens.clone().count();
+    for t in &rest[..used] {
+        written.push_str(&t.to_string());
+    }
+
+    Key {
+        action,
+        label,
+        written,
+        span,
+        location: file::location(),
+        caption: parse_caption(tokens),
+    }
+}
+
+/// Parses a key, and its hold action if it's a tap-hold key
+fn parse_action(context: &Context, t: TokenTree, tokens: &mut Peekable<IntoIter>) -> TokenStream {
+    let punct = match &t {
+        TokenTree::Punct(p) => Some(p.as_char()),
+        _ => None,
+    };
+    let key = parse_key(context, t);
+    match (tokens.peek(), punct) {
+        // `. / RShift` is more likely the `.` and `/` keys with a key missing
+        // than a tap-hold key, so the slash has to be quoted either way
+        (Some(TokenTree::Punct(p)), Some(c)) if p.as_char() == '/' => {
+            emit_error!(p, "`/` after the `{}` key is ambiguous", c; help = "To create a slash keycode, enclose it in apostrophes: '/'. To make `{}` a tap-hold key, enclose it instead: '{}'/hold", c, c);
+            key
+        }
+        (Some(TokenTree::Punct(p)), _) if p.as_char() == '/' => {
+            parse_hold_tap(context, key, tokens)
+        }
+        _ => key,
+    }
+}
+
+/// How long a tap-hold key has to be held before it counts as held, in ms
+const DEFAULT_HOLD_TAP_TIMEOUT: u16 = 200;
+
+/// Parses the `/hold` and optional `/timeout` after the tap action of a
+/// tap-hold key, e.g. `Escape/LCtrl` or `Space/(1)/300`
+fn parse_hold_tap(
+    context: &Context,
+    tap: TokenStream,
+    tokens: &mut Peekable<IntoIter>,
+) -> TokenStream {
+    let slash = tokens.next().unwrap();
+    let hold = match tokens.next() {
+        Some(t) => parse_key(context, t),
+        None => {
+            emit_error!(slash, "Expected a hold action after `/`"; help = "To create a slash keycode, enclose it in apostrophes: '/'");
+            return tap;
+        }
+    };
+
+    let mut timeout = DEFAULT_HOLD_TAP_TIMEOUT;
+    if let Some(TokenTree::Punct(p)) = tokens.peek() {
+        if p.as_char() == '/' {
+            let slash = tokens.next().unwrap();
+            match tokens.next() {
+                Some(TokenTree::Literal(l)) => match l.to_string().parse() {
+                    Ok(ms) => timeout = ms,
+                    Err(_) => emit_error!(l, "Expected a timeout in ms, from 0 to {}", u16::MAX),
+                },
+                Some(t) => emit_error!(t, "Expected a timeout in ms after the hold action"),
+                None => emit_error!(slash, "Expected a timeout in ms after `/`"),
+            }
+        }
+    }
+
+    quote! {
+        keyberon::action::Action::HoldTap {
+            timeout: #timeout,
+            hold: &#hold,
+            tap: &#tap,
+            config: keyberon::action::HoldTapConfig::Default,
+            tap_hold_interval: 0,
+        }
+    }
+}
+
+fn parse_key(context: &Context, t: TokenTree) -> TokenStream {
+    match t {
+        TokenTree::Ident(i) => match i.to_string().as_str() {
+            "n" => quote! { keyberon::action::Action::NoOp },
+            "t" => quote! { keyberon::action::Action::Trans },
+            "_" => char_action(context, '_', i.span()),
+            _ if context.aliases.iter().any(|(name, _)| *name == i) => parse_alias_key(context, &i),
+            // `Mute` and the volume keys are keycodes too, but only some hosts
+            // take them from the keyboard
+            name if keycode::consumer(name).is_some()
+                && (context.custom.is_some() || keycode::lookup(name).is_none()) =>
+            {
+                let usage = Literal::u16_unsuffixed(keycode::consumer(name).unwrap());
+                context.custom_action(i.span(), quote! { Consumer(#usage) })
+            }
+            name if keycode::system(name).is_some() => {
+                let usage = Literal::u8_unsuffixed(keycode::system(name).unwrap());
+                context.custom_action(i.span(), quote! { System(#usage) })
+            }
+            name if keycode::mouse(name).is_some() => {
+                let action: TokenStream = keycode::mouse(name).unwrap().parse().unwrap();
+                context.custom_action(i.span(), action)
+            }
+            name => match keycode::lookup(name) {
+                Some(k) => {
+                    let k = Ident::new(k, i.span());
+                    quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::#k) }
+                }
+                None => {
+                    match keycode::suggest(name) {
+                        Some(k) => {
+                            emit_error!(i, "Unknown keycode `{}`", i; help = "Did you mean `{}`?", k)
+                        }
+                        None => {
+                            emit_error!(i, "Unknown keycode `{}`", i; help = "Keycodes are the variants of keyberon::key_code::KeyCode")
+                        }
+                    }
+                    quote! { keyberon::action::Action::NoOp }
+                }
+            },
+        },
+        TokenTree::Punct(p) => char_action(context, p.as_char(), p.span()),
+        TokenTree::Literal(l) => literal_to_keycode(context, &l),
+        TokenTree::Group(g) => parse_group(context, &g),
+    }
+}
+
+/// Parses the key an alias was given for, where the alias is used
+fn parse_alias_key(context: &Context, alias: &Ident) -> TokenStream {
+    if context.expanding.borrow().contains(alias) {
+        emit_error!(alias, "Alias `{}` is used in its own key", alias);
+        return quote! { keyberon::action::Action::NoOp };
+    }
+    let (_, key) = context
+        .aliases
+        .iter()
+        .find(|(name, _)| name == alias)
+        .unwrap();
+
+    context.expanding.borrow_mut().push(alias.clone());
+    let mut tokens = key.clone().into_iter().peekable();
+    let action = parse_action(context, tokens.next().unwrap(), &mut tokens);
+    if let Some(t) = tokens.next() {
+        emit_error!(t, "Expected one key for alias `{}`", alias; help = "Keys can be pressed together with [ ... ]");
+    }
+    context.expanding.borrow_mut().pop();
+    action
+}
+
+fn parse_group(context: &Context, g: &Group) -> TokenStream {
     match g.delimiter() {
         // Handle empty groups
         Delimiter::Parenthesis if g.stream().is_empty() => {
-            emit_error!(g, "Expected a layer number in layer switch"; help = "To create a parenthesis keycode, enclose it in apostrophes: '('")
+            emit_error!(g, "Expected a layer number in layer switch"; help = "To create a parenthesis keycode, enclose it in apostrophes: '('");
+            quote! { keyberon::action::Action::NoOp }
         }
         Delimiter::Brace if g.stream().is_empty() => {
-            emit_error!(g, "Expected an action - group cannot be empty"; help = "To create a brace keycode, enclose it in apostrophes: '{'")
+            emit_error!(g, "Expected an action - group cannot be empty"; help = "To create a brace keycode, enclose it in apostrophes: '{'");
+            quote! { keyberon::action::Action::NoOp }
         }
         Delimiter::Bracket if g.stream().is_empty() => {
-            emit_error!(g, "Expected keycodes - keycode group cannot be empty"; help = "To create a bracket keycode, enclose it in apostrophes: '['")
+            emit_error!(g, "Expected keycodes - keycode group cannot be empty"; help = "To create a bracket keycode, enclose it in apostrophes: '['");
+            quote! { keyberon::action::Action::NoOp }
         }
 
-        // Momentary layer switch (Action::Layer)
-        Delimiter::Parenthesis => {
-            let tokens = g.stream();
-            out.extend(quote! { keyberon::action::Action::Layer(#tokens), });
-        }
-        // Pass the expression unchanged (adding a comma after it)
-        Delimiter::Brace => out.extend(g.stream().into_iter().chain(TokenStream::from(
-            TokenTree::Punct(Punct::new(',', Spacing::Alone)),
-        ))),
+        Delimiter::Parenthesis => parse_layer_switch(context, g),
+        // Pass the expression unchanged
+        Delimiter::Brace => {
+            context.opaque.set(true);
+            g.stream()
+        }
         // Multiple keycodes (Action::MultipleKeyCodes)
-        Delimiter::Bracket => parse_keycode_group(g.stream(), out),
+        Delimiter::Bracket => parse_keycode_group(context, g.stream()),
 
         // Is this reachable?
-        Delimiter::None => emit_error!(g, "Unexpected group"),
+        Delimiter::None => {
+            emit_error!(g, "Unexpected group");
+            quote! { keyberon::action::Action::NoOp }
+        }
     }
 }
 
-fn parse_keycode_group(input: TokenStream, out: &mut TokenStream) {
-    let mut inner = TokenStream::new();
-    for t in input {
-        match t {
-            TokenTree::Ident(i) => inner.extend(quote! {
-                keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::#i),
-            }),
-            TokenTree::Punct(p) => punctuation_to_keycode(&p, &mut inner),
-            TokenTree::Literal(l) => literal_to_keycode(&l, &mut inner),
-            TokenTree::Group(g) => parse_group(&g, &mut inner),
-        }
-    }
-    out.extend(quote! { keyberon::action::Action::MultipleActions(&[#inner]) });
-}
-
-fn punctuation_to_keycode(p: &Punct, out: &mut TokenStream) {
-    match p.as_char() {
-        // Normal punctuation
-        '-' => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Minus), }),
-        '=' => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Equal), }),
-        ';' => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::SColon), }),
-        ',' => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Comma), }),
-        '.' => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Dot), }),
-        '/' => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Slash), }),
-
-        // Shifted punctuation
-        '!' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb1]), }),
-        '@' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb2]), }),
-        '#' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb3]), }),
-        '$' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb4]), }),
-        '%' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb5]), }),
-        '^' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb6]), }),
-        '&' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb7]), }),
-        '*' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb8]), }),
-        '_' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Minus]), }),
-        '+' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Equal]), }),
-        '|' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Bslash]), }),
-        '~' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Grave]), }),
-        '<' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Comma]), }),
-        '>' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Dot]), }),
-        '?' => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Slash]), }),
-        // Is this reachable?
-        _ => emit_error!(p, "Punctuation could not be parsed as a keycode")
+fn parse_layer_switch(context: &Context, g: &Group) -> TokenStream {
+    let mut tokens = g.stream().into_iter().peekable();
+
+    let kind = match tokens.peek() {
+        Some(TokenTree::Ident(i)) if i == "toggle" || i == "oneshot" || i == "default" => {
+            let kind = i.clone();
+            tokens.next();
+            Some(kind)
+        }
+        _ => None,
+    };
+    let layer = context.layer_index(tokens.collect());
+
+    let switch = match &kind {
+        None => lint::SwitchKind::Momentary,
+        Some(kind) if kind == "toggle" => lint::SwitchKind::Toggle,
+        Some(kind) if kind == "oneshot" => lint::SwitchKind::OneShot,
+        Some(_) => lint::SwitchKind::Default,
+    };
+    match layer_number(&layer) {
+        Some(target) => {
+            let (layer, key) = context.position.get();
+            context.switches.borrow_mut().push(lint::LayerSwitch {
+                kind: switch,
+                layer,
+                key,
+                target,
+                span: g.span(),
+                location: file::location(),
+            });
+        }
+        None => context.opaque.set(true),
+    }
+
+    match kind {
+        Some(kind) if layer.is_empty() => {
+            emit_error!(kind, "Expected a layer number after `{}`", kind);
+            quote! { keyberon::action::Action::NoOp }
+        }
+        // Momentary layer switch (Action::Layer)
+        None => {
+            let mut tokens = layer.clone().into_iter();
+            if let (Some(TokenTree::Ident(i)), Some(TokenTree::Literal(_))) =
+                (tokens.next(), tokens.next())
+            {
+                emit_error!(i, "Unknown layer switch `{}`", i; help = "Expected `toggle`, `oneshot` or `default`");
+            }
+            quote! { keyberon::action::Action::Layer(#layer) }
+        }
+        Some(kind) if kind == "toggle" => {
+            context.custom_action(g.span(), quote! { ToggleLayer(#layer) })
+        }
+        Some(kind) if kind == "oneshot" => {
+            context.custom_action(g.span(), quote! { OneShotLayer(#layer) })
+        }
+        // The firmware needs to know the default layer to switch back to it
+        // from toggled and one-shot layers, when it has a custom action type
+        Some(_) => match context.custom {
+            Some(_) => context.custom_action(g.span(), quote! { DefaultLayer(#layer) }),
+            None => quote! { keyberon::action::Action::DefaultLayer(#layer) },
+        },
     }
 }
 
-fn literal_to_keycode(l: &Literal, out: &mut TokenStream) {
-    //let repr = l.to_string();
-    match l.to_string().as_str() {
-        "1" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb1), }),
-        "2" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb2), }),
-        "3" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb3), }),
-        "4" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb4), }),
-        "5" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb5), }),
-        "6" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb6), }),
-        "7" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb7), }),
-        "8" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb8), }),
-        "9" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb9), }),
-        "0" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Kb0), }),
+/// The number in a layer switch, if it's just a number
+fn layer_number(layer: &TokenStream) -> Option<usize> {
+    let mut tokens = layer.clone().into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(l)), None) => l.to_string().parse().ok(),
+        _ => None,
+    }
+}
 
-        // Char literals; mostly punctuation which can't be properly tokenized alone
-        r#"'\''"# => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Quote), }),
-        r#"'\\'"# => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Bslash), }),
-        // Shifted characters
-        "'['" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::LBracket), }),
-        "']'" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::RBracket), }),
-        "'`'" => out.extend(quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::Grave), }),
-        "'\"'" => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Quote]), }),
-        "'('" => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb9]), }),
-        "')'" => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Kb0]), }),
-        "'{'" => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::LBracket]), }),
-        "'}'" => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::RBracket]), }),
-        "'_'" => out.extend(quote! { keyberon::action::Action::MultipleKeyCodes(&[keyberon::key_code::KeyCode::LShift, keyberon::key_code::KeyCode::Minus]), }),
+fn parse_keycode_group(context: &Context, input: TokenStream) -> TokenStream {
+    let mut inner = Vec::new();
+    let mut tokens = input.into_iter().peekable();
+    while let Some(t) = tokens.next() {
+        inner.push(parse_action(context, t, &mut tokens));
+    }
+    quote! { keyberon::action::Action::MultipleActions(&[#(#inner),*]) }
+}
 
-        s if s.starts_with('\'') => emit_error!(l, "Literal could not be parsed as a keycode"; help = "Maybe try without quotes?"),
+/// `Action::KeyCode` for the named keycode
+fn keycode(name: &str) -> TokenStream {
+    let k = Ident::new(name, Span::call_site());
+    quote! { keyberon::action::Action::KeyCode(keyberon::key_code::KeyCode::#k) }
+}
 
-        s if s.starts_with('\"')  => {
-            if s.len() == 3 {
-                emit_error!(l, "Typing strings on key press is not yet supported"; help = "Did you mean to use apostrophes instead of quotes?");
-            } else {
-                emit_error!(l, "Typing strings on key press is not yet supported");
+fn literal_to_keycode(context: &Context, l: &Literal) -> TokenStream {
+    match l.to_string().as_str() {
+        // Numbers are the keys on the number row, whatever they type
+        s if s.len() == 1 && s.chars().all(|c| c.is_ascii_digit()) => keycode(&format!("Kb{}", s)),
+
+        // Char literals type the character on the host layout
+        s if s.starts_with('\'') => match char_value(s) {
+            Some(c) => char_action(context, c, l.span()),
+            None => {
+                emit_error!(l, "Character literal could not be parsed"; help = "Only \\n, \\t, \\r, \\0, \\\\, \\', \\\", \\x and \\u{...} escapes are supported");
+                quote! { keyberon::action::Action::NoOp }
             }
+        },
+
+        s if s.starts_with('"') || s.starts_with('r') => type_string(context, l),
+
+        _ => {
+            emit_error!(l, "Literal could not be parsed as a keycode"; help = "Numbers from 0 to 9 are keys, other characters can be written in apostrophes, e.g. '('");
+            quote! { keyberon::action::Action::NoOp }
         }
-        _ => emit_error!(l, "Literal could not be parsed as a keycode")
     }
 }
-// take non-matrix layout
 
-// get each layer
+/// The action that types a character on the host layout, holding Shift or
+/// AltGr for characters that need them
+fn char_action(context: &Context, c: char, span: Span) -> TokenStream {
+    let host = context.host;
+    match host.chords(c).as_deref() {
+        Some([chord]) => {
+            return match (&chord_keycodes(chord)[..], &context.custom) {
+                ([k], _) => quote! { keyberon::action::Action::KeyCode(#k) },
+                // The firmware keeps the modifiers to the report the symbol
+                // is typed in, when it has a custom action type
+                (keys, Some(_)) => context.custom_action(span, quote! { Symbol(&[#(#keys),*]) }),
+                (keys, None) => {
+                    quote! { keyberon::action::Action::MultipleKeyCodes(&[#(#keys),*]) }
+                }
+            };
+        }
+        Some(_) => {
+            emit_error!(span, "{:?} is typed with a dead key on {}, so it can't be a key on its own", c, host.name(); help = "It can be typed in a string instead: {:?}", c.to_string());
+        }
+        None => cant_type(span, c, host),
+    }
+    quote! { keyberon::action::Action::NoOp }
+}
 
-// combine the streams of each row and shift everything around?
+/// The keycodes pressed together for a chord
+fn chord_keycodes(chord: &Chord) -> Vec<TokenStream> {
+    let key = Ident::new(chord.key, Span::call_site());
+    let mut keycodes = Vec::new();
+    if chord.shift {
+        keycodes.push(quote! { keyberon::key_code::KeyCode::LShift });
+    }
+    if chord.alt_gr {
+        keycodes.push(quote! { keyberon::key_code::KeyCode::RAlt });
+    }
+    keycodes.push(quote! { keyberon::key_code::KeyCode::#key });
+    keycodes
+}
 
-// un-combine in the matrix 13x5
+fn cant_type(span: Span, c: char, host: Host) {
+    emit_error!(span, "Character {:?} can't be typed on {}", c, host.name(); help = "The host layout is set with `host \"en-US\";`, one of {}", Host::names());
+}
 
-// parse like normal
+/// `CustomAction::TypeString` that types out the characters of a string
+/// literal, one chord of keycodes per character, or two for characters typed
+/// with a dead key
+fn type_string(context: &Context, l: &Literal) -> TokenStream {
+    let value = match string_value(&l.to_string()) {
+        Some(value) => value,
+        None => {
+            emit_error!(l, "String could not be parsed"; help = "Only \\n, \\t, \\r, \\0, \\\\, \\', \\\", \\x and \\u{...} escapes are supported");
+            return quote! { keyberon::action::Action::NoOp };
+        }
+    };
+
+    let mut chords = Vec::new();
+    for c in value.chars() {
+        match context.host.chords(c) {
+            Some(typed) => chords.extend(typed.iter().map(|chord| {
+                let keycodes = chord_keycodes(chord);
+                quote! { &[#(#keycodes),*] }
+            })),
+            None => cant_type(l.span(), c, context.host),
+        }
+    }
+
+    context.custom_action(l.span(), quote! { TypeString(&[#(#chords),*]) })
+}
+
+/// The value of a char literal from its source representation, or `None` if
+/// it uses an escape that isn't supported
+fn char_value(repr: &str) -> Option<char> {
+    let inner = repr.strip_prefix('\'')?.strip_suffix('\'')?;
+    let value = string_value(&format!("\"{}\"", inner))?;
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// The value of a string literal from its source representation, or `None`
+/// if it isn't a string or uses an escape that isn't supported
+fn string_value(repr: &str) -> Option<String> {
+    if let Some(raw) = repr.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return raw
+            .get(hashes + 1..raw.len().checked_sub(hashes + 1)?)
+            .map(str::to_string);
+    }
+
+    let mut chars = repr
+        .strip_prefix('"')?
+        .strip_suffix('"')?
+        .chars()
+        .peekable();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            '0' => value.push('\0'),
+            c @ ('\\' | '\'' | '"') => value.push(c),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                value.push(char::from(u8::from_str_radix(&hex, 16).ok()?));
+            }
+            'u' => {
+                let hex: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // A `\` at the end of a line skips the line break and indentation
+            '\n' => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+            _ => return None,
+        }
+    }
+    Some(value)
+}
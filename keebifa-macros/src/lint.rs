@@ -0,0 +1,167 @@
+use crate::{file, Context, Key};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote_spanned;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchKind {
+    Momentary,
+    Toggle,
+    OneShot,
+    Default,
+}
+
+/// A key that switches to a layer
+pub struct LayerSwitch {
+    pub kind: SwitchKind,
+    /// Layer and key index of the key
+    pub layer: usize,
+    pub key: usize,
+    /// The layer it switches to
+    pub target: usize,
+    pub span: Span,
+    /// Where the key is written, if it's from a layout file
+    pub location: Option<String>,
+}
+
+/// Checks the layer switches of the whole layout. Switching to a layer that
+/// doesn't exist is an error, and layers that can't be reached or that trap
+/// the keyboard are warned about. Returns the code for the warnings.
+pub fn lint(context: &Context, layers: &[Vec<Key>], spans: &[Span]) -> TokenStream {
+    let all = context.switches.borrow();
+    for s in all.iter().filter(|s| s.target >= layers.len()) {
+        file::at(s.location.clone());
+        emit_error!(
+            s.span,
+            "There is no layer {}, the layout has {} layers",
+            s.target,
+            layers.len()
+        );
+    }
+    file::at(None);
+    let switches: Vec<&LayerSwitch> = all.iter().filter(|s| s.target < layers.len()).collect();
+
+    let mut warnings = TokenStream::new();
+
+    // A held layer that covers the key holding it makes it do something else
+    // while the layer is up, which is never what was meant
+    for s in switches.iter().filter(|s| s.kind == SwitchKind::Momentary) {
+        let key = match layers[s.target].get(s.key) {
+            Some(key) if s.target != s.layer => key,
+            _ => continue,
+        };
+        let holds_itself = switches.iter().any(|other| {
+            other.kind == SwitchKind::Momentary
+                && (other.layer, other.key, other.target) == (s.target, s.key, s.target)
+        });
+        if key.label != "t" && key.label != "n" && !holds_itself {
+            warnings.extend(warning(
+                key.span,
+                &key.location,
+                &format!(
+                    "Layer {} covers the key that holds it, make this key `t`",
+                    context.layer_name(s.target)
+                ),
+            ));
+        }
+    }
+
+    // Keys with expressions could switch to any layer, so which layers can be
+    // reached can't be worked out
+    if context.opaque.get() {
+        return warnings;
+    }
+
+    let from_zero = reachable(&switches, 0, |_| true);
+    for (layer, span) in spans.iter().enumerate() {
+        if !from_zero.contains(&layer) {
+            warnings.extend(warning(
+                *span,
+                &None,
+                &format!(
+                    "Layer {} can't be reached from layer 0",
+                    context.layer_name(layer)
+                ),
+            ));
+        }
+    }
+
+    // A toggled layer needs a way back, toggling it off or switching to the
+    // default layer, on itself, on a layer it can hold, or under one of its
+    // `t` keys on a default layer
+    let defaults: Vec<usize> = switches
+        .iter()
+        .filter(|s| s.kind == SwitchKind::Default)
+        .map(|s| s.target)
+        .chain([0])
+        .collect();
+    let mut checked = Vec::new();
+    for s in switches.iter().filter(|s| s.kind == SwitchKind::Toggle) {
+        if s.layer == s.target || checked.contains(&s.target) {
+            continue;
+        }
+        checked.push(s.target);
+
+        let held = reachable(&switches, s.target, |s| {
+            matches!(s.kind, SwitchKind::Momentary | SwitchKind::OneShot)
+        });
+        let way_back = switches.iter().any(|other| {
+            let under_t = defaults.contains(&other.layer)
+                && matches!(layers[s.target].get(other.key), Some(key) if key.label == "t");
+            let back = match other.kind {
+                SwitchKind::Toggle => other.target == s.target,
+                kind => kind == SwitchKind::Default,
+            };
+            (held.contains(&other.layer) || under_t) && back
+        });
+        if !way_back {
+            warnings.extend(warning(
+                s.span,
+                &s.location,
+                &format!(
+                    "Layer {} has no key to toggle it off or switch to the default layer",
+                    context.layer_name(s.target)
+                ),
+            ));
+        }
+    }
+
+    warnings
+}
+
+/// The layers that can be switched to from a layer, including itself, using
+/// the kinds of layer switch accepted by `follow`
+fn reachable(
+    switches: &[&LayerSwitch],
+    from: usize,
+    follow: impl Fn(&LayerSwitch) -> bool,
+) -> Vec<usize> {
+    let mut layers = vec![from];
+    let mut i = 0;
+    while let Some(&layer) = layers.get(i) {
+        for s in switches.iter().filter(|s| s.layer == layer && follow(s)) {
+            if !layers.contains(&s.target) {
+                layers.push(s.target);
+            }
+        }
+        i += 1;
+    }
+    layers
+}
+
+/// Proc macros can't emit warnings on stable, so this uses a deprecated item
+/// to get rustc to warn with the message at the span
+fn warning(span: Span, location: &Option<String>, message: &str) -> TokenStream {
+    let message = match location {
+        Some(location) => format!("{}, at {}", message, location),
+        None => message.to_string(),
+    };
+    let name = Ident::new("keymap_warning", span);
+    quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #message)]
+            #[allow(non_camel_case_types)]
+            struct #name;
+            let _ = #name;
+        };
+    }
+}
@@ -0,0 +1,316 @@
+//! Reading the layers of a QMK `keymap.json`, as layers written the way they
+//! are in `alice_layout!`
+
+use crate::file;
+use proc_macro2::{Delimiter, Group, Span, TokenStream};
+use std::fs;
+use std::path::Path;
+
+/// QMK keycodes, their aliases, and the key they are in `alice_layout!`
+#[rustfmt::skip]
+const KEYCODES: &[(&[&str], &str)] = &[
+    (&["KC_ENTER", "KC_ENT"], "Enter"),
+    (&["KC_ESCAPE", "KC_ESC"], "Escape"),
+    (&["KC_BACKSPACE", "KC_BSPACE", "KC_BSPC"], "BSpace"),
+    (&["KC_TAB"], "Tab"),
+    (&["KC_SPACE", "KC_SPC"], "Space"),
+    (&["KC_MINUS", "KC_MINS"], "Minus"),
+    (&["KC_EQUAL", "KC_EQL"], "Equal"),
+    (&["KC_LEFT_BRACKET", "KC_LBRACKET", "KC_LBRC"], "LBracket"),
+    (&["KC_RIGHT_BRACKET", "KC_RBRACKET", "KC_RBRC"], "RBracket"),
+    (&["KC_BACKSLASH", "KC_BSLASH", "KC_BSLS"], "Bslash"),
+    (&["KC_NONUS_HASH", "KC_NUHS"], "NonUsHash"),
+    (&["KC_SEMICOLON", "KC_SCOLON", "KC_SCLN"], "SColon"),
+    (&["KC_QUOTE", "KC_QUOT"], "Quote"),
+    (&["KC_GRAVE", "KC_GRV"], "Grave"),
+    (&["KC_COMMA", "KC_COMM"], "Comma"),
+    (&["KC_DOT"], "Dot"),
+    (&["KC_SLASH", "KC_SLSH"], "Slash"),
+    (&["KC_CAPS_LOCK", "KC_CAPSLOCK", "KC_CAPS", "KC_CLCK"], "CapsLock"),
+    (&["KC_PRINT_SCREEN", "KC_PSCREEN", "KC_PSCR"], "PScreen"),
+    (&["KC_SCROLL_LOCK", "KC_SCROLLLOCK", "KC_SCRL", "KC_SLCK"], "ScrollLock"),
+    (&["KC_PAUSE", "KC_PAUS", "KC_BRK"], "Pause"),
+    (&["KC_INSERT", "KC_INS"], "Insert"),
+    (&["KC_HOME"], "Home"),
+    (&["KC_PAGE_UP", "KC_PGUP"], "PgUp"),
+    (&["KC_DELETE", "KC_DEL"], "Delete"),
+    (&["KC_END"], "End"),
+    (&["KC_PAGE_DOWN", "KC_PGDOWN", "KC_PGDN"], "PgDown"),
+    (&["KC_RIGHT", "KC_RGHT"], "Right"),
+    (&["KC_LEFT"], "Left"),
+    (&["KC_DOWN"], "Down"),
+    (&["KC_UP"], "Up"),
+    (&["KC_NUM_LOCK", "KC_NUMLOCK", "KC_NUM", "KC_NLCK"], "NumLock"),
+    (&["KC_KP_SLASH", "KC_PSLS"], "KpSlash"),
+    (&["KC_KP_ASTERISK", "KC_PAST"], "KpAsterisk"),
+    (&["KC_KP_MINUS", "KC_PMNS"], "KpMinus"),
+    (&["KC_KP_PLUS", "KC_PPLS"], "KpPlus"),
+    (&["KC_KP_ENTER", "KC_PENT"], "KpEnter"),
+    (&["KC_KP_DOT", "KC_PDOT"], "KpDot"),
+    (&["KC_KP_EQUAL", "KC_PEQL"], "KpEqual"),
+    (&["KC_NONUS_BACKSLASH", "KC_NONUS_BSLASH", "KC_NUBS"], "NonUsBslash"),
+    (&["KC_APPLICATION", "KC_APP"], "Application"),
+    (&["KC_LEFT_CTRL", "KC_LCTRL", "KC_LCTL"], "LCtrl"),
+    (&["KC_LEFT_SHIFT", "KC_LSHIFT", "KC_LSFT"], "LShift"),
+    (&["KC_LEFT_ALT", "KC_LALT", "KC_LOPT"], "LAlt"),
+    (&["KC_LEFT_GUI", "KC_LGUI", "KC_LCMD", "KC_LWIN"], "LGui"),
+    (&["KC_RIGHT_CTRL", "KC_RCTRL", "KC_RCTL"], "RCtrl"),
+    (&["KC_RIGHT_SHIFT", "KC_RSHIFT", "KC_RSFT"], "RShift"),
+    (&["KC_RIGHT_ALT", "KC_RALT", "KC_ROPT", "KC_ALGR"], "RAlt"),
+    (&["KC_RIGHT_GUI", "KC_RGUI", "KC_RCMD", "KC_RWIN"], "RGui"),
+    (&["KC_AUDIO_MUTE", "KC_MUTE"], "Mute"),
+    (&["KC_AUDIO_VOL_UP", "KC_VOLU"], "VolUp"),
+    (&["KC_AUDIO_VOL_DOWN", "KC_VOLD"], "VolDown"),
+    (&["KC_MEDIA_PLAY_PAUSE", "KC_MPLY"], "MediaPlayPause"),
+    (&["KC_MEDIA_NEXT_TRACK", "KC_MNXT"], "MediaNextSong"),
+    (&["KC_MEDIA_PREV_TRACK", "KC_MPRV"], "MediaPreviousSong"),
+    (&["KC_MEDIA_STOP", "KC_MSTP"], "MediaStop"),
+    (&["KC_MS_UP", "KC_MS_U"], "MsUp"),
+    (&["KC_MS_DOWN", "KC_MS_D"], "MsDown"),
+    (&["KC_MS_LEFT", "KC_MS_L"], "MsLeft"),
+    (&["KC_MS_RIGHT", "KC_MS_R"], "MsRight"),
+    (&["KC_MS_BTN1", "KC_BTN1"], "MsBtn1"),
+    (&["KC_MS_BTN2", "KC_BTN2"], "MsBtn2"),
+    (&["KC_MS_BTN3", "KC_BTN3"], "MsBtn3"),
+    (&["KC_MS_BTN4", "KC_BTN4"], "MsBtn4"),
+    (&["KC_MS_BTN5", "KC_BTN5"], "MsBtn5"),
+    (&["KC_MS_WH_UP", "KC_WH_U"], "WhUp"),
+    (&["KC_MS_WH_DOWN", "KC_WH_D"], "WhDown"),
+    (&["KC_MS_WH_LEFT", "KC_WH_L"], "WhLeft"),
+    (&["KC_MS_WH_RIGHT", "KC_WH_R"], "WhRight"),
+    (&["KC_MEDIA_EJECT", "KC_EJCT"], "MediaEjectCD"),
+    (&["KC_CALCULATOR", "KC_CALC"], "MediaCalc"),
+];
+
+/// QMK's shifted keycodes, and the character they type. They're typed like
+/// a character in `'...'`, so on the host layout rather than always with
+/// Shift and the US key.
+#[rustfmt::skip]
+const SHIFTED: &[(&[&str], char)] = &[
+    (&["KC_TILDE", "KC_TILD"], '~'),
+    (&["KC_EXCLAIM", "KC_EXLM"], '!'),
+    (&["KC_AT"], '@'),
+    (&["KC_HASH"], '#'),
+    (&["KC_DOLLAR", "KC_DLR"], '$'),
+    (&["KC_PERCENT", "KC_PERC"], '%'),
+    (&["KC_CIRCUMFLEX", "KC_CIRC"], '^'),
+    (&["KC_AMPERSAND", "KC_AMPR"], '&'),
+    (&["KC_ASTERISK", "KC_ASTR"], '*'),
+    (&["KC_LEFT_PAREN", "KC_LPRN"], '('),
+    (&["KC_RIGHT_PAREN", "KC_RPRN"], ')'),
+    (&["KC_UNDERSCORE", "KC_UNDS"], '_'),
+    (&["KC_PLUS"], '+'),
+    (&["KC_LEFT_CURLY_BRACE", "KC_LCBR"], '{'),
+    (&["KC_RIGHT_CURLY_BRACE", "KC_RCBR"], '}'),
+    (&["KC_PIPE"], '|'),
+    (&["KC_COLON", "KC_COLN"], ':'),
+    (&["KC_DOUBLE_QUOTE", "KC_DQUO", "KC_DQT"], '"'),
+    (&["KC_LEFT_ANGLE_BRACKET", "KC_LABK", "KC_LT"], '<'),
+    (&["KC_RIGHT_ANGLE_BRACKET", "KC_RABK", "KC_GT"], '>'),
+    (&["KC_QUESTION", "KC_QUES"], '?'),
+];
+
+/// QMK's modifier keycodes, for `LCTL(kc)`, `MT(MOD_LCTL, kc)` and
+/// `LCTL_T(kc)`, and the modifiers they hold
+#[rustfmt::skip]
+const MODIFIERS: &[(&[&str], &[&str])] = &[
+    (&["LCTL", "C", "MOD_LCTL", "LCTL_T", "CTL_T"], &["LCtrl"]),
+    (&["LSFT", "S", "MOD_LSFT", "LSFT_T", "SFT_T"], &["LShift"]),
+    (&["LALT", "A", "LOPT", "MOD_LALT", "LALT_T", "ALT_T", "LOPT_T", "OPT_T"], &["LAlt"]),
+    (&["LGUI", "G", "LCMD", "LWIN", "MOD_LGUI", "LGUI_T", "GUI_T", "LCMD_T", "CMD_T", "LWIN_T", "WIN_T"], &["LGui"]),
+    (&["RCTL", "MOD_RCTL", "RCTL_T"], &["RCtrl"]),
+    (&["RSFT", "MOD_RSFT", "RSFT_T"], &["RShift"]),
+    (&["RALT", "ALGR", "ROPT", "MOD_RALT", "RALT_T", "ALGR_T", "ROPT_T"], &["RAlt"]),
+    (&["RGUI", "RCMD", "RWIN", "MOD_RGUI", "RGUI_T", "RCMD_T", "RWIN_T"], &["RGui"]),
+    (&["MEH", "MOD_MEH", "MEH_T", "KC_MEH"], &["LCtrl", "LShift", "LAlt"]),
+    (&["HYPR", "MOD_HYPR", "HYPR_T", "ALL_T", "KC_HYPR"], &["LCtrl", "LShift", "LAlt", "LGui"]),
+];
+
+fn modifiers(name: &str) -> Option<&'static [&'static str]> {
+    MODIFIERS
+        .iter()
+        .find(|(names, _)| names.contains(&name))
+        .map(|&(_, mods)| mods)
+}
+
+/// Reads the layers of a QMK keymap, as layers for `alice_layout!` with rows
+/// of the lengths in `shape`. Keycodes that can't be converted are errors at
+/// `span` and are left as `n`.
+pub fn read_keymap(path: &Path, span: Span, shape: &[usize]) -> Vec<Group> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) => abort!(span, "Couldn't read {}: {}", path.display(), e),
+    };
+    let keymap: serde_json::Value = match serde_json::from_str(&json) {
+        Ok(keymap) => keymap,
+        Err(e) => abort!(span, "{} isn't a QMK keymap: {}", path.display(), e),
+    };
+    let layers = match keymap.get("layers").and_then(|l| l.as_array()) {
+        Some(layers) => layers,
+        None => abort!(span, "{} has no `layers` list", path.display()),
+    };
+
+    let count: usize = shape.iter().sum();
+    let mut out = Vec::new();
+    for (index, layer) in layers.iter().enumerate() {
+        let keycodes = match layer.as_array() {
+            Some(keycodes) if keycodes.len() == count => keycodes,
+            Some(keycodes) => abort!(
+                span,
+                "Expected {} keys in layer {} of {}, found {}",
+                count,
+                index,
+                path.display(),
+                keycodes.len()
+            ),
+            None => abort!(
+                span,
+                "Layer {} of {} isn't a list of keycodes",
+                index,
+                path.display()
+            ),
+        };
+        let mut keys = keycodes.iter().enumerate().map(|(key, keycode)| {
+            let converted = match keycode.as_str() {
+                Some(keycode) => convert(keycode.trim()),
+                None => Err(format!("`{}` isn't a keycode", keycode)),
+            };
+            converted.unwrap_or_else(|e| {
+                emit_error!(span, "{}, in layer {} key {}", e, index, key + 1; help = "Change the key in the keymap, or write the layer in the layout");
+                "n".to_string()
+            })
+        });
+
+        let mut rows = String::new();
+        for &len in shape {
+            let row: Vec<String> = keys.by_ref().take(len).collect();
+            rows.push_str(&format!("[{}]\n", row.join(" ")));
+        }
+        let rows: TokenStream = match rows.parse() {
+            Ok(rows) => rows,
+            Err(e) => abort!(
+                span,
+                "Layer {} of {} couldn't be read: {}",
+                index,
+                path.display(),
+                e
+            ),
+        };
+        out.push(Group::new(Delimiter::Brace, file::respan(rows, span)));
+    }
+    out
+}
+
+/// Converts a QMK keycode to a key as it's written in a layout
+fn convert(keycode: &str) -> Result<String, String> {
+    let (name, args) = match keycode.split_once('(') {
+        Some((name, rest)) => match rest.strip_suffix(')') {
+            Some(args) => (name.trim(), split_args(args)),
+            None => return Err(format!("`{}` is missing a `)`", keycode)),
+        },
+        None => (keycode, Vec::new()),
+    };
+
+    let layer = |arg: &str| match arg.parse::<usize>() {
+        Ok(layer) => Ok(layer),
+        Err(_) => Err(format!("`{}` in `{}` isn't a layer number", arg, keycode)),
+    };
+    let mods = |arg: &str| -> Result<String, String> {
+        let mut held = Vec::new();
+        for m in arg.split('|').map(str::trim) {
+            match modifiers(m) {
+                Some(mods) => held.extend_from_slice(mods),
+                None => return Err(format!("`{}` in `{}` isn't a modifier", m, keycode)),
+            }
+        }
+        Ok(match &held[..] {
+            [m] => m.to_string(),
+            _ => format!("[{}]", held.join(" ")),
+        })
+    };
+
+    match (name, &args[..]) {
+        ("KC_TRANSPARENT" | "KC_TRNS" | "_______", []) => Ok("t".to_string()),
+        ("KC_NO" | "XXXXXXX", []) => Ok("n".to_string()),
+        ("MO", [n]) => Ok(format!("({})", layer(n)?)),
+        ("TG", [n]) => Ok(format!("(toggle {})", layer(n)?)),
+        ("OSL", [n]) => Ok(format!("(oneshot {})", layer(n)?)),
+        ("DF", [n]) => Ok(format!("(default {})", layer(n)?)),
+        ("LT", [n, tap]) => Ok(format!("{}/({})", convert(tap)?, layer(n)?)),
+        ("MT", [m, tap]) => Ok(format!("{}/{}", convert(tap)?, mods(m)?)),
+        (name, [tap]) if name.ends_with("_T") && modifiers(name).is_some() => {
+            Ok(format!("{}/{}", convert(tap)?, mods(name)?))
+        }
+        (name, [key]) if modifiers(name).is_some() => Ok(format!(
+            "[{} {}]",
+            mods(name)?,
+            unwrap_group(&convert(key)?)
+        )),
+        (name, []) if name == "KC_MEH" || name == "KC_HYPR" => mods(name),
+        (name, []) => keycode_name(name)
+            .ok_or_else(|| format!("QMK keycode `{}` can't be used with keyberon", name)),
+        _ => Err(format!(
+            "QMK keycode `{}` can't be used with keyberon",
+            keycode
+        )),
+    }
+}
+
+/// The keyberon keycode, keycodes in `[ ... ]` or character in `'...'` for a
+/// QMK keycode
+fn keycode_name(name: &str) -> Option<String> {
+    if let Some((_, c)) = SHIFTED.iter().find(|(names, _)| names.contains(&name)) {
+        return Some(format!("{:?}", c));
+    }
+    if let Some((_, key)) = KEYCODES.iter().find(|(names, _)| names.contains(&name)) {
+        return Some(key.to_string());
+    }
+
+    let key = name.strip_prefix("KC_")?;
+    let numbered = |prefix: &str, max: usize| {
+        let n: usize = key.strip_prefix(prefix)?.parse().ok()?;
+        (n <= max).then_some(n)
+    };
+    match key {
+        _ if key.len() == 1 && key.chars().all(|c| c.is_ascii_uppercase()) => Some(key.to_string()),
+        _ if key.len() == 1 && key.chars().all(|c| c.is_ascii_digit()) => {
+            Some(format!("Kb{}", key))
+        }
+        _ => {
+            if let Some(n) = numbered("F", 24).filter(|&n| n > 0) {
+                Some(format!("F{}", n))
+            } else {
+                let n = numbered("KP_", 9).or_else(|| numbered("P", 9))?;
+                Some(format!("Kp{}", n))
+            }
+        }
+    }
+}
+
+/// Splits the arguments of a QMK keycode at the commas that aren't in
+/// another keycode's arguments
+fn split_args(args: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    out.push(args[start..].trim());
+    out
+}
+
+/// The keys in a `[ ... ]` group, or the key if it's just one
+fn unwrap_group(key: &str) -> &str {
+    key.strip_prefix('[')
+        .and_then(|k| k.strip_suffix(']'))
+        .unwrap_or(key)
+}
@@ -0,0 +1,178 @@
+//! Drawings of the layers of a layout, as SVG and as ASCII for terminals and
+//! code review
+
+use crate::geometry::{Geometry, Place};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How far each half of a split keyboard is turned, in degrees
+const ANGLE: f32 = 8.0;
+
+/// A layer to draw
+pub struct Drawing {
+    pub name: String,
+    pub description: Option<String>,
+    /// What's drawn on each key, in the order they're written
+    pub keys: Vec<String>,
+}
+
+/// Writes an SVG and a text drawing of each layer to `dir`, named after the
+/// layer. Files that haven't changed aren't written again.
+pub fn render(dir: &Path, geometry: &Geometry, layers: &[Drawing]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let places = geometry.places();
+    for layer in layers {
+        let svg = svg(layer, &places, geometry.is_split());
+        write_if_changed(&dir.join(format!("{}.svg", layer.name)), &svg)?;
+        write_if_changed(
+            &dir.join(format!("{}.txt", layer.name)),
+            &ascii(layer, &places),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_if_changed(path: &Path, contents: &str) -> io::Result<()> {
+    match fs::read_to_string(path) {
+        Ok(old) if old == contents => Ok(()),
+        _ => fs::write(path, contents),
+    }
+}
+
+/// Size of a key unit in the SVG, in px
+const UNIT: f32 = 54.0;
+
+fn svg(layer: &Drawing, places: &[Place], split: bool) -> String {
+    let width = places.iter().map(|p| p.x + p.width).fold(0.0, f32::max) * UNIT;
+    let rows = places.iter().map(|p| p.row + 1).max().unwrap_or(0);
+    let height = rows as f32 * UNIT;
+    // Room for the title and for the corners of the turned halves
+    let margin = UNIT * 2.0;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" font-family="sans-serif" font-size="11">"#,
+        -margin,
+        -margin,
+        width + margin * 2.0,
+        height + margin * 2.0,
+    );
+    let _ = writeln!(
+        out,
+        r#"<text x="{}" y="{}" text-anchor="middle" font-size="16">{}</text>"#,
+        width / 2.0,
+        -margin / 2.0,
+        escape(&layer.name)
+    );
+    if let Some(description) = &layer.description {
+        let _ = writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-size="12">{}</text>"#,
+            width / 2.0,
+            -margin / 2.0 + 18.0,
+            escape(description)
+        );
+    }
+
+    let halves: &[bool] = match split {
+        true => &[true, false],
+        false => &[true],
+    };
+    for &left in halves {
+        // The halves turn about their inner bottom corners, so they're
+        // further apart at the top
+        let bottom = places.iter().filter(|p| p.row == rows - 1);
+        let (pivot, angle) = match left {
+            _ if !split => (0.0, 0.0),
+            true => {
+                let right_edge = bottom.filter(|p| p.left).map(|p| p.x + p.width);
+                (right_edge.fold(0.0, f32::max), -ANGLE)
+            }
+            false => {
+                let left_edge = bottom.filter(|p| !p.left).map(|p| p.x);
+                (left_edge.fold(f32::MAX, f32::min), ANGLE)
+            }
+        };
+        let _ = writeln!(
+            out,
+            r#"<g transform="rotate({} {} {})">"#,
+            angle,
+            pivot * UNIT,
+            height
+        );
+        let keys = places.iter().zip(&layer.keys);
+        for (place, key) in keys.filter(|(p, _)| p.left == left) {
+            let (x, y) = (place.x * UNIT, place.row as f32 * UNIT);
+            let w = place.width * UNIT;
+            let _ = writeln!(
+                out,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" rx="4" fill="#f4f4f4" stroke="#333"/>"##,
+                x + 1.0,
+                y + 1.0,
+                w - 2.0,
+                UNIT - 2.0
+            );
+            let _ = writeln!(
+                out,
+                r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+                x + w / 2.0,
+                y + UNIT / 2.0 + 4.0,
+                escape(key)
+            );
+        }
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Characters in a key unit in the text drawing
+const CHARS: f32 = 7.0;
+
+fn ascii(layer: &Drawing, places: &[Place]) -> String {
+    let col = |x: f32| (x * CHARS).round() as usize;
+    let width = places.iter().map(|p| col(p.x + p.width)).max().unwrap_or(0) + 1;
+    let rows = places.iter().map(|p| p.row + 1).max().unwrap_or(0);
+    let mut grid = vec![vec![' '; width]; rows * 2 + 1];
+
+    for (place, key) in places.iter().zip(&layer.keys) {
+        let (x0, x1) = (col(place.x), col(place.x + place.width));
+        let y = place.row * 2;
+        for line in [y, y + 2] {
+            let last = x1 - x0;
+            for (i, c) in grid[line][x0..=x1].iter_mut().enumerate() {
+                if *c != '+' {
+                    *c = if i == 0 || i == last { '+' } else { '-' };
+                }
+            }
+        }
+        grid[y + 1][x0] = '|';
+        grid[y + 1][x1] = '|';
+
+        let room = x1 - x0 - 1;
+        let label: Vec<char> = key.chars().take(room).collect();
+        let start = x0 + 1 + (room - label.len()) / 2;
+        grid[y + 1][start..start + label.len()].copy_from_slice(&label);
+    }
+
+    let mut out = match &layer.description {
+        Some(description) => format!("{}: {}\n", layer.name, description),
+        None => format!("{}\n", layer.name),
+    };
+    for line in grid {
+        let line: String = line.into_iter().collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
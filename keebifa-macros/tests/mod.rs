@@ -1,12 +1,23 @@
 extern crate keebifa_macros;
 use keebifa_macros::alice_layout;
 use keyberon::action::Action;
+use keyberon::key_code::KeyCode;
 
 #[cfg(test)]
 #[test]
 #[rustfmt::skip]
 fn test_convert_layer() {
-    pub static array: [[Action<core::convert::Infallible>; 65];1] =  alice_layout! {
+    alice_layout! {
+        pub static LAYOUT;
+
+        wiring {
+            [(3, 0) (1, 0) (0, 0) (0, 1) (0, 2) (0, 3) (0, 4) (0, 5) (0, 6) (0, 7) (0, 8) (0, 9) (0, 10) (0, 11) (0, 12)]
+            [(4, 0) (2, 0) (1, 1) (1, 2) (1, 3) (1, 4) (1, 5) (2, 6) (1, 6) (1, 7) (1, 8) (1, 9) (1, 10) (1, 11) (1, 12)]
+            [(4, 1) (3, 1) (2, 1) (2, 2) (2, 3) (2, 4) (2, 5) (3, 7) (2, 7) (2, 8) (2, 9) (2, 10) (2, 11) (2, 12)]
+            [(4, 2) (3, 2) (3, 3) (3, 4) (3, 5) (3, 6) (4, 7) (3, 8) (4, 9) (3, 9) (3, 10) (4, 11) (3, 11) (3, 12)]
+            [(4, 3) (4, 4) (4, 5) (4, 6) (4, 8) (4, 10) (4, 12)]
+        }
+
         {
             [ 1 2 3 4 5 6 7 8 9 0 Q W E R T ]
             [ 1 2 3 4 5 6 7 8 9 0 Q W E R T ]
@@ -14,7 +25,572 @@ fn test_convert_layer() {
             [ 1 2 3 4 5 6 7 8 9 0 Q W E R ]
             [ 1 2 3 4 5 6 7 ]
         }
+    }
+
+    println!("{:?}", LAYOUT);
+    assert_eq!(LAYOUT[0][3][0], Action::KeyCode(KeyCode::Kb1));
+    assert_eq!(LAYOUT[0][0][0], Action::KeyCode(KeyCode::Kb3));
+    assert_eq!(LAYOUT[0][2][6], Action::KeyCode(KeyCode::Kb8));
+    assert_eq!(LAYOUT[0][4][12], Action::KeyCode(KeyCode::Kb7));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomAction {
+    TypeString(&'static [&'static [KeyCode]]),
+    Symbol(&'static [KeyCode]),
+    DefaultLayer(usize),
+    ToggleLayer(usize),
+    OneShotLayer(usize),
+    Consumer(u16),
+    System(u8),
+    MouseMove(i8, i8),
+    MouseButton(u8),
+    MouseWheel(i8, i8),
+    MousePrecision,
+}
+
+/// Wires each key to the same row and column in the matrix as it's written
+/// in, so `LAYOUT[layer][row][col]` is the key in that place in the layer. A
+/// layer written as just its top row, `[A B C]`, has `n` for the rest of its
+/// keys.
+macro_rules! test_layout {
+    // A top row's hold actions and labels are part of the key before them,
+    // and every other key takes the place of one of the `n`s. An `@` before
+    // a group is a key, not a label.
+    (@row [$($done:tt)*] [$($keys:tt)*] [/ $part:tt $($row:tt)*] $ns:tt $($rest:tt)*) => {
+        test_layout!(@row [$($done)*] [$($keys)* / $part] [$($row)*] $ns $($rest)*)
+    };
+    (@row [$($done:tt)*] [$($keys:tt)*] [@ ($($key:tt)*) $($row:tt)*] [n n $($ns:tt)*] $($rest:tt)*) => {
+        test_layout!(@row [$($done)*] [$($keys)* @ ($($key)*)] [$($row)*] [$($ns)*] $($rest)*)
+    };
+    (@row [$($done:tt)*] [$($keys:tt)*] [@ $label:tt $($row:tt)*] $ns:tt $($rest:tt)*) => {
+        test_layout!(@row [$($done)*] [$($keys)* @ $label] [$($row)*] $ns $($rest)*)
+    };
+    (@row [$($done:tt)*] [$($keys:tt)*] [$key:tt $($row:tt)*] [n $($ns:tt)*] $($rest:tt)*) => {
+        test_layout!(@row [$($done)*] [$($keys)* $key] [$($row)*] [$($ns)*] $($rest)*)
+    };
+    (@row [$($done:tt)*] [$($keys:tt)*] [] [$($ns:tt)*] $($rest:tt)*) => {
+        test_layout!(@layers [$($done)* {
+            [$($keys)* $($ns)*]
+            [n n n n n n n n n n n n n n n]
+            [n n n n n n n n n n n n n n]
+            [n n n n n n n n n n n n n n]
+            [n n n n n n n]
+        }] $($rest)*)
+    };
+    // Brackets after `=` or `#` aren't a layer
+    (@layers [$($done:tt)*] = $value:tt $($rest:tt)*) => {
+        test_layout!(@layers [$($done)* = $value] $($rest)*)
+    };
+    (@layers [$($done:tt)*] # $attr:tt $($rest:tt)*) => {
+        test_layout!(@layers [$($done)* # $attr] $($rest)*)
+    };
+    (@layers [$($done:tt)*] [$($row:tt)*] $($rest:tt)*) => {
+        test_layout!(@row [$($done)*] [] [$($row)*] [n n n n n n n n n n n n n n n] $($rest)*)
+    };
+    (@layers [$($done:tt)*] $next:tt $($rest:tt)*) => {
+        test_layout!(@layers [$($done)* $next] $($rest)*)
+    };
+    (@layers [$($layers:tt)*]) => {
+        alice_layout! {
+            static LAYOUT;
+            custom CustomAction;
+
+            wiring {
+                [(0, 0) (0, 1) (0, 2) (0, 3) (0, 4) (0, 5) (0, 6) (0, 7) (0, 8) (0, 9) (0, 10) (0, 11) (0, 12) (0, 13) (0, 14)]
+                [(1, 0) (1, 1) (1, 2) (1, 3) (1, 4) (1, 5) (1, 6) (1, 7) (1, 8) (1, 9) (1, 10) (1, 11) (1, 12) (1, 13) (1, 14)]
+                [(2, 0) (2, 1) (2, 2) (2, 3) (2, 4) (2, 5) (2, 6) (2, 7) (2, 8) (2, 9) (2, 10) (2, 11) (2, 12) (2, 13)]
+                [(3, 0) (3, 1) (3, 2) (3, 3) (3, 4) (3, 5) (3, 6) (3, 7) (3, 8) (3, 9) (3, 10) (3, 11) (3, 12) (3, 13)]
+                [(4, 0) (4, 1) (4, 2) (4, 3) (4, 4) (4, 5) (4, 6)]
+            }
+
+            $($layers)*
+        }
+    };
+    ($($layers:tt)*) => {
+        test_layout!(@layers [] $($layers)*)
     };
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_unwired_positions() {
+    test_layout! {
+        {
+            [A n n n n n n n n n n n n n n]
+            [n n n n n n n n n n n n n n n]
+            [n n n n n n n n n n n n n B]
+            [n n n n n n n n n n n n n n]
+            [n n n n n n n]
+        }
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::KeyCode(KeyCode::A));
+    assert_eq!(LAYOUT[0][2][13], Action::KeyCode(KeyCode::B));
+    assert_eq!(LAYOUT[0][2][14], Action::NoOp);
+    assert_eq!(LAYOUT[0][4][14], Action::NoOp);
+}
 
-    println!("{:?}", array);
+#[test]
+#[rustfmt::skip]
+fn test_type_string() {
+    test_layout! {
+        ["Hi!\n"]
+    }
+
+    assert_eq!(
+        LAYOUT[0][0][0],
+        Action::Custom(CustomAction::TypeString(&[
+            &[KeyCode::LShift, KeyCode::H],
+            &[KeyCode::I],
+            &[KeyCode::LShift, KeyCode::Kb1],
+            &[KeyCode::Enter],
+        ]))
+    );
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_hold_tap() {
+    use keyberon::action::HoldTapConfig;
+
+    test_layout! {
+        [Escape/LCtrl Space/(1)/300 '/']
+        {
+            [t t t t t t t t t t t t t t t]
+            [t t t t t t t t t t t t t t t]
+            [t t t t t t t t t t t t t t]
+            [t t t t t t t t t t t t t t]
+            [t t t t t t t]
+        }
+    }
+
+    assert_eq!(
+        LAYOUT[0][0][0],
+        Action::HoldTap {
+            timeout: 200,
+            hold: &Action::KeyCode(KeyCode::LCtrl),
+            tap: &Action::KeyCode(KeyCode::Escape),
+            config: HoldTapConfig::Default,
+            tap_hold_interval: 0,
+        }
+    );
+    assert_eq!(
+        LAYOUT[0][0][1],
+        Action::HoldTap {
+            timeout: 300,
+            hold: &Action::Layer(1),
+            tap: &Action::KeyCode(KeyCode::Space),
+            config: HoldTapConfig::Default,
+            tap_hold_interval: 0,
+        }
+    );
+    assert_eq!(LAYOUT[0][0][2], Action::KeyCode(KeyCode::Slash));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_layer_switches() {
+    test_layout! {
+        [(1) (toggle 1) (oneshot 1) (default 1)]
+        {
+            [t (toggle 1) t t t t t t t t t t t t t]
+            [t t t t t t t t t t t t t t t]
+            [t t t t t t t t t t t t t t]
+            [t t t t t t t t t t t t t t]
+            [t t t t t t t]
+        }
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::Layer(1));
+    assert_eq!(LAYOUT[0][0][1], Action::Custom(CustomAction::ToggleLayer(1)));
+    assert_eq!(LAYOUT[0][0][2], Action::Custom(CustomAction::OneShotLayer(1)));
+    assert_eq!(LAYOUT[0][0][3], Action::Custom(CustomAction::DefaultLayer(1)));
+
+    // The switches the firmware holds toggled and one-shot layers with
+    assert_eq!(LAYOUT_SWITCHES, 5);
+    assert_eq!(LAYOUT[1][5][0], Action::Layer(0));
+    assert_eq!(LAYOUT[1][5][1], Action::Layer(1));
+    assert_eq!(LAYOUT[1][5][2], Action::NoOp);
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_named_layers() {
+    test_layout! {
+        layer base [(nav) Space/(nav) (1)]
+        []
+        layer nav [t t (default base)]
+    }
+
+    assert_eq!(NAV, 2);
+    assert_eq!(LAYOUT[BASE][0][0], Action::Layer(2));
+    assert_eq!(LAYOUT[NAV][0][2], Action::Custom(CustomAction::DefaultLayer(0)));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_row_lengths() {
+    // A tap-hold key is one key, however many tokens it's written with
+    test_layout! {
+        [Escape/LCtrl n n n n n n n n n n n n n A]
+    }
+
+    assert_eq!(LAYOUT[0][0][14], Action::KeyCode(KeyCode::A));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_keycode_aliases() {
+    test_layout! {
+        [Esc Bksp Del F24]
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::KeyCode(KeyCode::Escape));
+    assert_eq!(LAYOUT[0][0][1], Action::KeyCode(KeyCode::BSpace));
+    assert_eq!(LAYOUT[0][0][2], Action::KeyCode(KeyCode::Delete));
+    assert_eq!(LAYOUT[0][0][3], Action::KeyCode(KeyCode::F24));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_media_and_system_keys() {
+    test_layout! {
+        [VolUp Play BrightDown MediaPlayPause SysSleep SysWake]
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::Custom(CustomAction::Consumer(0xe9)));
+    assert_eq!(LAYOUT[0][0][1], Action::Custom(CustomAction::Consumer(0xcd)));
+    assert_eq!(LAYOUT[0][0][2], Action::Custom(CustomAction::Consumer(0x70)));
+    assert_eq!(LAYOUT[0][0][3], Action::KeyCode(KeyCode::MediaPlayPause));
+    assert_eq!(LAYOUT[0][0][4], Action::Custom(CustomAction::System(0x82)));
+    assert_eq!(LAYOUT[0][0][5], Action::Custom(CustomAction::System(0x83)));
+
+    // Without a custom action type the volume keys are keycodes
+    alice_layout! {
+        static KEYCODES;
+        geometry [2];
+        wiring { [(0, 0) (0, 1)] }
+        { [VolUp Mute] }
+    }
+
+    assert_eq!(KEYCODES[0][0][0], Action::KeyCode(KeyCode::VolUp));
+    assert_eq!(KEYCODES[0][0][1], Action::KeyCode(KeyCode::Mute));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_mouse_keys() {
+    test_layout! {
+        [MsUp MsRight MsBtn1 MsBtn3 WhDown WhRight MsSlow]
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::Custom(CustomAction::MouseMove(0, -1)));
+    assert_eq!(LAYOUT[0][0][1], Action::Custom(CustomAction::MouseMove(1, 0)));
+    assert_eq!(LAYOUT[0][0][2], Action::Custom(CustomAction::MouseButton(1)));
+    assert_eq!(LAYOUT[0][0][3], Action::Custom(CustomAction::MouseButton(4)));
+    assert_eq!(LAYOUT[0][0][4], Action::Custom(CustomAction::MouseWheel(-1, 0)));
+    assert_eq!(LAYOUT[0][0][5], Action::Custom(CustomAction::MouseWheel(0, 1)));
+    assert_eq!(LAYOUT[0][0][6], Action::Custom(CustomAction::MousePrecision));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_characters() {
+    test_layout! {
+        [: _ '<' '|' 'A' 'a' '(' '\'' '"' '`' ' ' '\n']
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::SColon])));
+    assert_eq!(LAYOUT[0][0][1], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Minus])));
+    assert_eq!(LAYOUT[0][0][2], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Comma])));
+    assert_eq!(LAYOUT[0][0][3], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Bslash])));
+    assert_eq!(LAYOUT[0][0][4], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::A])));
+    assert_eq!(LAYOUT[0][0][5], Action::KeyCode(KeyCode::A));
+    assert_eq!(LAYOUT[0][0][6], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Kb9])));
+    assert_eq!(LAYOUT[0][0][7], Action::KeyCode(KeyCode::Quote));
+    assert_eq!(LAYOUT[0][0][8], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Quote])));
+    assert_eq!(LAYOUT[0][0][9], Action::KeyCode(KeyCode::Grave));
+    assert_eq!(LAYOUT[0][0][10], Action::KeyCode(KeyCode::Space));
+    assert_eq!(LAYOUT[0][0][11], Action::KeyCode(KeyCode::Enter));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_host_layout() {
+    test_layout! {
+        host "de-DE";
+
+        [n '@' 'z' : Y 1 "^z"]
+    }
+
+    assert_eq!(LAYOUT[0][0][1], Action::Custom(CustomAction::Symbol(&[KeyCode::RAlt, KeyCode::Q])));
+    assert_eq!(LAYOUT[0][0][2], Action::KeyCode(KeyCode::Y));
+    assert_eq!(LAYOUT[0][0][3], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Dot])));
+    assert_eq!(LAYOUT[0][0][4], Action::KeyCode(KeyCode::Y));
+    assert_eq!(LAYOUT[0][0][5], Action::KeyCode(KeyCode::Kb1));
+    assert_eq!(
+        LAYOUT[0][0][6],
+        Action::Custom(CustomAction::TypeString(&[&[KeyCode::Grave], &[KeyCode::Space], &[KeyCode::Y]]))
+    );
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_aliases() {
+    test_layout! {
+        alias Hyper = [LCtrl LAlt LShift LGui];
+        alias CapsCtrl = CapsLock/LCtrl;
+        alias Shortcut = CtrlV;
+        alias CtrlV = [LCtrl V];
+
+        [Hyper CapsCtrl Shortcut]
+    }
+
+    assert_eq!(
+        LAYOUT[0][0][0],
+        Action::MultipleActions(&[
+            Action::KeyCode(KeyCode::LCtrl),
+            Action::KeyCode(KeyCode::LAlt),
+            Action::KeyCode(KeyCode::LShift),
+            Action::KeyCode(KeyCode::LGui),
+        ])
+    );
+    assert!(matches!(LAYOUT[0][0][1], Action::HoldTap { .. }));
+    assert_eq!(
+        LAYOUT[0][0][2],
+        Action::MultipleActions(&[Action::KeyCode(KeyCode::LCtrl), Action::KeyCode(KeyCode::V)])
+    );
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_override_layers() {
+    test_layout! {
+        layer base {
+            [Escape H J '/' (nav) n n n n n n n n n n]
+            [n n n n n n n n n n n n n n n]
+            [n n n n n n n n n n n n n n]
+            [n n n n n n n n n n n n n n]
+            [n n n n n n Space]
+        }
+        layer nav : base {
+            H => Left,
+            J => Down/LCtrl,
+            '/' => '?',
+            (5, 1) => (more),
+            (5, 7) => Enter,
+        }
+        layer more : nav {
+            Left => Home
+        }
+    }
+
+    assert_eq!(LAYOUT[NAV][0][0], Action::KeyCode(KeyCode::Escape));
+    assert_eq!(LAYOUT[NAV][0][1], Action::KeyCode(KeyCode::Left));
+    assert!(matches!(LAYOUT[NAV][0][2], Action::HoldTap { .. }));
+    assert_eq!(LAYOUT[NAV][0][3], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Slash])));
+    assert_eq!(LAYOUT[NAV][0][4], Action::Layer(NAV));
+    assert_eq!(LAYOUT[NAV][4][6], Action::KeyCode(KeyCode::Enter));
+    assert_eq!(LAYOUT[MORE][0][1], Action::KeyCode(KeyCode::Home));
+    assert_eq!(LAYOUT[MORE][4][6], Action::KeyCode(KeyCode::Enter));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_labels() {
+    test_layout! {
+        layer base @ "Typing" [Escape @ "escape" Space/LCtrl @ "space or ctrl" @ (nav)]
+        layer nav : base {
+            Escape => Grave @ "grave",
+        }
+    }
+
+    assert_eq!(LAYOUT_LABELS[BASE][0][0], "escape");
+    assert_eq!(LAYOUT_LABELS[BASE][0][1], "space or ctrl");
+    assert_eq!(LAYOUT_LABELS[BASE][0][2], "@");
+    assert_eq!(LAYOUT_LABELS[BASE][0][3], "(nav)");
+    assert_eq!(LAYOUT_LABELS[BASE][2][14], "");
+    assert!(matches!(LAYOUT[BASE][0][2], Action::Custom(CustomAction::Symbol(_))));
+    assert_eq!(LAYOUT[NAV][0][0], Action::KeyCode(KeyCode::Grave));
+    assert_eq!(LAYOUT_LABELS[NAV][0][0], "grave");
+    assert_eq!(LAYOUT_LABELS[NAV][0][1], "space or ctrl");
+    assert_eq!(LAYOUT_LAYERS, [("base", "Typing"), ("nav", "")]);
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_geometry() {
+    alice_layout! {
+        static LAYOUT;
+        geometry ortho5x12;
+
+        wiring {
+            [(0, 0) (0, 1) (0, 2) (0, 3) (0, 4) (0, 5) (0, 6) (0, 7) (0, 8) (0, 9) (0, 10) (0, 11)]
+            [(1, 0) (1, 1) (1, 2) (1, 3) (1, 4) (1, 5) (1, 6) (1, 7) (1, 8) (1, 9) (1, 10) (1, 11)]
+            [(2, 0) (2, 1) (2, 2) (2, 3) (2, 4) (2, 5) (2, 6) (2, 7) (2, 8) (2, 9) (2, 10) (2, 11)]
+            [(3, 0) (3, 1) (3, 2) (3, 3) (3, 4) (3, 5) (3, 6) (3, 7) (3, 8) (3, 9) (3, 10) (3, 11)]
+            [(4, 0) (4, 1) (4, 2) (4, 3) (4, 4) (4, 5) (4, 6) (4, 7) (4, 8) (4, 9) (4, 10) (4, 11)]
+        }
+
+        layer base {
+            [Tab Q W E R T Y U I O P BSpace]
+            [Escape A S D F G H J K L ; Enter]
+            [LShift Z X C V B N M , . '/' RShift]
+            [n n n n n n n n n n n n]
+            [LCtrl LGui LAlt n (raise) Space Space (raise) Left Down Up Right]
+        }
+        layer raise : base {
+            (1, 12) => Delete,
+            (5, 5) => t, (5, 8) => t,
+        }
+    }
+    assert_eq!(LAYOUT[0][1][11], Action::KeyCode(KeyCode::Enter));
+    assert_eq!(LAYOUT[0][4][4], Action::Layer(RAISE));
+    assert_eq!(LAYOUT[RAISE][0][11], Action::KeyCode(KeyCode::Delete));
+    assert_eq!(LAYOUT[RAISE][0][0], Action::KeyCode(KeyCode::Tab));
+
+    mod declared {
+        use keebifa_macros::alice_layout;
+
+        alice_layout! {
+            pub static LAYOUT;
+            geometry [3 2];
+
+            wiring {
+                [(0, 0) (0, 1) (0, 2)]
+                [(1, 0) (1, 1)]
+            }
+
+            {
+                [A B C]
+                [D E]
+            }
+        }
+    }
+    assert_eq!(declared::LAYOUT[0][1][1], Action::KeyCode(KeyCode::E));
+}
+
+#[test]
+#[rustfmt::skip]
+#[allow(unexpected_cfgs)]
+fn test_features() {
+    // None of these features are enabled, so the expansion without them is
+    // the one that's built
+    test_layout! {
+        #[cfg(feature = "mac")]
+        host "en-GB";
+        #[cfg(feature = "mac")]
+        alias home = { Action::KeyCode(KeyCode::Home) };
+
+        layer base {
+            [Esc '`' 1 2 3 4 5 6 7 8 9 0 - = BSpace]
+            #[cfg(feature = "gaming")]
+            [n Tab Q W E R T Y U I O P n n n]
+            #[cfg(not(feature = "gaming"))]
+            [n Tab Q W E R T Y U I O P '[' ']' '\\']
+            [n Caps A S D F G H J K L ; '\'' Enter]
+            [LShift Z X C V B B N M , . '/' RShift (nav)]
+            [n #[cfg(feature = "mac")] LGui #[cfg(not(feature = "mac"))] LAlt Space #[cfg(not(feature = "mac"))] LGui #[cfg(feature = "mac")] LAlt Space RAlt RCtrl]
+        }
+        #[cfg(any(feature = "gaming", all(feature = "mac", not(feature = "work"))))]
+        layer gaming : base {
+            W => Up,
+        }
+        layer nav : base {
+            H => Left,
+            #[cfg(feature = "mac")]
+            J => home,
+            #[cfg(not(feature = "mac"))]
+            J => Down/LCtrl/300,
+        }
+    }
+
+    assert_eq!(LAYOUT.len(), 2);
+    assert_eq!(LAYOUT_LAYER_COUNT, 2);
+    assert_eq!(NAV, 1);
+    assert_eq!(LAYOUT[BASE][1][2], Action::KeyCode(KeyCode::Q));
+    assert_eq!(LAYOUT[BASE][4][1], Action::KeyCode(KeyCode::LAlt));
+    assert_eq!(LAYOUT[BASE][4][3], Action::KeyCode(KeyCode::LGui));
+    assert_eq!(LAYOUT[BASE][0][1], Action::KeyCode(KeyCode::Grave));
+    assert!(matches!(LAYOUT[NAV][2][8], Action::HoldTap { tap: &Action::KeyCode(KeyCode::Down), .. }));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_render() {
+    test_layout! {
+        render "target/test-render";
+
+        layer base {
+            [Esc '`' 1 2 3 4 5 6 7 8 9 0 - = BSpace]
+            [n Tab Q W E R T Y U I O P '[' ']' '\\']
+            [n Caps/LCtrl A S D F G H J K L ; '\'' Enter]
+            [LShift Z X C V B B N M , . '/' RShift (nav)]
+            [n LAlt Space LGui Space RAlt RCtrl]
+        }
+        layer nav : base {
+            H => Left, J => Down, K => Up, L => Right,
+        }
+    }
+
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/target/test-render");
+    let text = std::fs::read_to_string(format!("{}/nav.txt", dir)).unwrap();
+    assert!(text.starts_with("nav\n"));
+    assert!(text.contains("| Left |"));
+    assert!(text.contains("|Caps/LCtrl |"));
+    assert!(text.contains("|  `   |"));
+    let svg = std::fs::read_to_string(format!("{}/nav.svg", dir)).unwrap();
+    assert_eq!(svg.matches("<rect").count(), 65);
+    assert!(svg.contains(">Left</text>"));
+}
+
+#[test]
+#[rustfmt::skip]
+fn test_include_keymap() {
+    test_layout! {
+        include_keymap!("tests/keymap.json");
+    }
+
+    assert_eq!(LAYOUT[0][0][0], Action::KeyCode(KeyCode::Escape));
+    assert_eq!(LAYOUT[0][0][2], Action::KeyCode(KeyCode::Kb1));
+    assert!(matches!(LAYOUT[0][2][1], Action::HoldTap { hold: &Action::KeyCode(KeyCode::LCtrl), tap: &Action::KeyCode(KeyCode::CapsLock), .. }));
+    assert_eq!(LAYOUT[0][3][13], Action::Layer(1));
+    assert!(matches!(LAYOUT[0][4][2], Action::HoldTap { hold: &Action::Layer(1), tap: &Action::KeyCode(KeyCode::Space), .. }));
+    assert_eq!(LAYOUT[0][4][6], Action::Custom(CustomAction::ToggleLayer(1)));
+    assert_eq!(LAYOUT[1][0][1], Action::Trans);
+    assert_eq!(LAYOUT[1][0][13], Action::KeyCode(KeyCode::F12));
+    assert_eq!(LAYOUT[1][1][2], Action::MultipleActions(&[Action::KeyCode(KeyCode::LCtrl), Action::KeyCode(KeyCode::C)]));
+    assert_eq!(LAYOUT[1][1][3], Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Kb1])));
+    assert!(matches!(LAYOUT[1][1][4], Action::HoldTap { hold: &Action::MultipleActions(_), .. }));
+    assert_eq!(LAYOUT[1][1][5], Action::Trans);
+    assert_eq!(LAYOUT[1][1][6], Action::NoOp);
+    assert_eq!(LAYOUT[1][2][7], Action::KeyCode(KeyCode::Left));
+}
+
+#[test]
+fn test_include_layout() {
+    test_layout! {
+        include_layout!("tests/layout.toml");
+    }
+
+    assert_eq!(LAYOUT[BASE][0][0], Action::KeyCode(KeyCode::Escape));
+    assert_eq!(LAYOUT[BASE][1][14], Action::KeyCode(KeyCode::NonUsHash));
+    assert!(matches!(
+        LAYOUT[BASE][2][1],
+        Action::HoldTap {
+            tap: &Action::KeyCode(KeyCode::CapsLock),
+            ..
+        }
+    ));
+    assert_eq!(LAYOUT[BASE][3][13], Action::Layer(NAV));
+    assert_eq!(LAYOUT[NAV][2][7], Action::KeyCode(KeyCode::Left));
+    assert_eq!(LAYOUT[NAV][2][10], Action::KeyCode(KeyCode::Right));
+    assert_eq!(
+        LAYOUT[NAV][3][11],
+        Action::Custom(CustomAction::Symbol(&[KeyCode::LShift, KeyCode::Kb2]))
+    );
+    assert_eq!(LAYOUT[NAV][0][0], Action::KeyCode(KeyCode::Escape));
+    assert_eq!(LAYOUT_LABELS[NAV][0][0], "escape");
+    assert_eq!(LAYOUT_LAYERS[NAV], ("nav", "Arrows on HJKL"));
 }
@@ -0,0 +1,8 @@
+/// Each file in `tests/ui` is a layout with a mistake in it, and the `.stderr`
+/// next to it the errors it gets. Run with `TRYBUILD=overwrite` to write the
+/// errors again after changing them.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,11 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    alias Hyper = ;
+    { [A B C] }
+}
+
+fn main() {}
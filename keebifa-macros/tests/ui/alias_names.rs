@@ -0,0 +1,14 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    alias Escape = A;
+    alias n = B;
+    alias Loop = [A Loop];
+    alias Two = A B;
+    { [Loop Two C] }
+}
+
+fn main() {}
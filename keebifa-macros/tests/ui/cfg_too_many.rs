@@ -0,0 +1,16 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    {
+        [
+            #[cfg(feature = "a")] A #[cfg(feature = "b")] B #[cfg(feature = "c")] C
+            #[cfg(feature = "d")] D #[cfg(feature = "e")] E #[cfg(feature = "f")] F
+            #[cfg(feature = "g")] G
+        ]
+    }
+}
+
+fn main() {}
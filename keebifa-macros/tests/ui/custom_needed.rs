@@ -0,0 +1,10 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    { [(toggle 0) (oneshot 0) "hi"] }
+}
+
+fn main() {}
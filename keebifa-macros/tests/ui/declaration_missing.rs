@@ -0,0 +1,6 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    pub static
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use keebifa_macros::alice_layout;
+
+#[allow(dead_code)]
+enum CustomAction {}
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    custom CustomAction;
+    host "de-DE";
+    { ['^' '£' "a£"] }
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [6];
+    wiring { [(0, 0) (0, 1) (0, 2) (0, 3) (0, 4) (0, 5)] }
+    { [() {} [] (toggle) (foo 1) (nav)] }
+}
+
+fn main() {}
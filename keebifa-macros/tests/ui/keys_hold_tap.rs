@@ -0,0 +1,18 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [2 2 2];
+    wiring {
+        [(0, 0) (0, 1)]
+        [(1, 0) (1, 1)]
+        [(2, 0) (2, 1)]
+    }
+    {
+        [A/LCtrl/x B/LCtrl/99999]
+        [n C/LCtrl/]
+        [n D/]
+    }
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3 2];
+    wiring {
+        [(0, 0) (0, 1) (0, 2)]
+        [(1, 0) (1, 1)]
+    }
+    {
+        [A B]
+        [C D E]
+        [F]
+    }
+    {
+        [A B C]
+    }
+}
+
+fn main() {}
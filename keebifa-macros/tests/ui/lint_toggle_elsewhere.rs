@@ -0,0 +1,21 @@
+#![deny(deprecated)]
+
+use keebifa_macros::alice_layout;
+
+#[allow(dead_code)]
+enum CustomAction {
+    DefaultLayer(usize),
+    ToggleLayer(usize),
+}
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    custom CustomAction;
+    { [(toggle 1) n n] }
+    { [(toggle 2) n n] }
+    { [(default 0) n n] }
+}
+
+fn main() {}
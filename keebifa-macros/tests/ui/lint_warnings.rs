@@ -0,0 +1,21 @@
+#![deny(deprecated)]
+
+use keebifa_macros::alice_layout;
+
+#[allow(dead_code)]
+enum CustomAction {
+    ToggleLayer(usize),
+}
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    custom CustomAction;
+    { [(1) (toggle 2) n] }
+    { [A n n] }
+    { [n n n] }
+    { [n n n] }
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    layer base { [A B B] }
+    layer nav : base {
+        A => X;
+        A => Z,
+        B => n,
+        C => n,
+        (2, 1) => n,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    layer base { [A B] }
+    layer nav : base {
+        (1, 3) => n,
+    }
+}
+
+fn main() {}
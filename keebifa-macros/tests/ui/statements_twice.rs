@@ -0,0 +1,23 @@
+use keebifa_macros::alice_layout;
+
+#[allow(dead_code)]
+enum CustomAction {}
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    wiring { [(0, 0) (0, 1) (0, 2)] }
+    custom CustomAction;
+    custom CustomAction;
+    host "en-US";
+    host "en-GB";
+    render "target/ui-render";
+    render "target/ui-render";
+    alias Hyper = A;
+    alias Hyper = B;
+    layer base { [A B C] }
+    layer base { [A B C] }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3];
+    { [A B C] }
+}
+
+fn main() {}
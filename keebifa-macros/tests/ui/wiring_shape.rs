@@ -0,0 +1,13 @@
+use keebifa_macros::alice_layout;
+
+alice_layout! {
+    static LAYOUT;
+    geometry [3 2];
+    wiring {
+        [(0, 0) (0, 1) (0, 1)]
+        [(1, 0)]
+        [(2, 0)]
+    }
+}
+
+fn main() {}
@@ -0,0 +1,200 @@
+//! The keyboard interfaces. BIOSes, UEFI setup and disk unlock prompts don't
+//! read report descriptors, so they ask the boot keyboard for the boot
+//! protocol with SET_PROTOCOL and expect the fixed 8 byte report from the HID
+//! spec. The NKRO keyboard's report is a bit for every key, which is too big
+//! for the 8 byte endpoint keyberon's class has.
+
+use keebifa_keys::report::{ReportProtocol, BOOT_REPORT_DESCRIPTOR, NKRO_REPORT_DESCRIPTOR};
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, Request, RequestType};
+use usb_device::UsbError;
+
+const HID_CLASS: u8 = 0x03;
+const BOOT_SUBCLASS: u8 = 0x01;
+const KEYBOARD_PROTOCOL: u8 = 0x01;
+
+const HID_DESCRIPTOR: u8 = 0x21;
+const REPORT_DESCRIPTOR: u8 = 0x22;
+
+const GET_REPORT: u8 = 0x01;
+const GET_IDLE: u8 = 0x02;
+const GET_PROTOCOL: u8 = 0x03;
+const SET_REPORT: u8 = 0x09;
+const SET_IDLE: u8 = 0x0a;
+const SET_PROTOCOL: u8 = 0x0b;
+
+/// How often the host polls for reports, in ms
+const POLL_INTERVAL: u8 = 10;
+
+/// A keyboard interface with an N byte report
+pub struct Keyboard<'a, B: UsbBus, const N: usize> {
+    interface: InterfaceNumber,
+    endpoint: EndpointIn<'a, B>,
+    report_descriptor: &'static [u8],
+    /// Whether it's a boot keyboard, which has the boot protocol
+    boot: bool,
+    /// The report for GET_REPORT, and the last one the endpoint took
+    report: [u8; N],
+    sent: [u8; N],
+    protocol: ReportProtocol,
+    used: bool,
+}
+
+pub type BootKeyboard<'a, B> = Keyboard<'a, B, 8>;
+pub type NkroKeyboard<'a, B> = Keyboard<'a, B, 29>;
+
+impl<'a, B: UsbBus> BootKeyboard<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Keyboard::with(alloc, BOOT_REPORT_DESCRIPTOR, true)
+    }
+}
+
+impl<'a, B: UsbBus> NkroKeyboard<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        Keyboard::with(alloc, NKRO_REPORT_DESCRIPTOR, false)
+    }
+}
+
+impl<'a, B: UsbBus, const N: usize> Keyboard<'a, B, N> {
+    fn with(alloc: &'a UsbBusAllocator<B>, report_descriptor: &'static [u8], boot: bool) -> Self {
+        Self {
+            interface: alloc.interface(),
+            endpoint: alloc.interrupt(N.next_power_of_two() as u16, POLL_INTERVAL),
+            report_descriptor,
+            boot,
+            report: [0; N],
+            sent: [0; N],
+            protocol: ReportProtocol::Report,
+            used: false,
+        }
+    }
+
+    /// The format the host wants the report in
+    pub fn protocol(&self) -> ReportProtocol {
+        self.protocol
+    }
+
+    /// Whether the host's HID driver has read the report descriptor since the
+    /// device was reset, so it knows what the reports are
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    /// Sends the report, unless it's the last one the endpoint took. Gives
+    /// whether the endpoint has it, for the host's next poll. It doesn't if
+    /// the last report hasn't been polled yet, so it's sent again on the next
+    /// tick.
+    pub fn send(&mut self, report: [u8; N]) -> bool {
+        self.report = report;
+        if report == self.sent {
+            return true;
+        }
+        match self.endpoint.write(&report) {
+            Ok(_) => {
+                self.sent = report;
+                true
+            }
+            Err(UsbError::WouldBlock) => false,
+            Err(e) => {
+                defmt::warn!("Keyboard report not sent: {}", defmt::Debug2Format(&e));
+                false
+            }
+        }
+    }
+
+    fn hid_descriptor(&self) -> [u8; 9] {
+        let [low, high] = (self.report_descriptor.len() as u16).to_le_bytes();
+        [
+            9,
+            HID_DESCRIPTOR,
+            0x11, // HID 1.11
+            0x01,
+            0x00, // Not localized
+            1,    // One report descriptor
+            REPORT_DESCRIPTOR,
+            low,
+            high,
+        ]
+    }
+
+    /// Whether a request is to this interface
+    fn is_for_me(&self, recipient: Recipient, index: u16) -> bool {
+        recipient == Recipient::Interface && index == u8::from(self.interface) as u16
+    }
+}
+
+impl<B: UsbBus, const N: usize> UsbClass<B> for Keyboard<'_, B, N> {
+    fn get_configuration_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        let (subclass, protocol) = match self.boot {
+            true => (BOOT_SUBCLASS, KEYBOARD_PROTOCOL),
+            false => (0, 0),
+        };
+        writer.interface(self.interface, HID_CLASS, subclass, protocol)?;
+        writer.write(HID_DESCRIPTOR, &self.hid_descriptor()[2..])?;
+        writer.endpoint(&self.endpoint)
+    }
+
+    /// Hosts start out with the report protocol, and ask for the boot
+    /// protocol again after a reset if they want it
+    fn reset(&mut self) {
+        self.report = [0; N];
+        self.sent = [0; N];
+        self.protocol = ReportProtocol::Report;
+        self.used = false;
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = xfer.request();
+        let (kind, request, value) = (req.request_type, req.request, req.value);
+        if !self.is_for_me(req.recipient, req.index) {
+            return;
+        }
+        let _ = match (kind, request) {
+            (RequestType::Standard, Request::GET_DESCRIPTOR) => match (value >> 8) as u8 {
+                REPORT_DESCRIPTOR => {
+                    self.used = true;
+                    xfer.accept_with_static(self.report_descriptor)
+                }
+                HID_DESCRIPTOR => xfer.accept_with(&self.hid_descriptor()),
+                _ => xfer.reject(),
+            },
+            (RequestType::Class, GET_REPORT) => xfer.accept_with(&self.report),
+            // Reports are only sent when they change, which is an idle rate
+            // of 0, whatever the host's asked for
+            (RequestType::Class, GET_IDLE) => xfer.accept_with(&[0]),
+            (RequestType::Class, GET_PROTOCOL) if self.boot => {
+                xfer.accept_with(&[self.protocol as u8])
+            }
+            (RequestType::Class, _) => xfer.reject(),
+            _ => Ok(()),
+        };
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = xfer.request();
+        let (kind, request, value) = (req.request_type, req.request, req.value);
+        if !self.is_for_me(req.recipient, req.index) || kind != RequestType::Class {
+            return;
+        }
+        let _ = match request {
+            // The LEDs, which the keyboard doesn't have
+            SET_REPORT => xfer.accept(),
+            SET_IDLE => xfer.accept(),
+            SET_PROTOCOL if self.boot => match value {
+                0 => {
+                    self.protocol = ReportProtocol::Boot;
+                    xfer.accept()
+                }
+                1 => {
+                    self.protocol = ReportProtocol::Report;
+                    xfer.accept()
+                }
+                _ => xfer.reject(),
+            },
+            _ => xfer.reject(),
+        };
+    }
+}
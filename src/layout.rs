@@ -1,73 +1,57 @@
 use keebifa_macros::alice_layout;
-use keyberon::action::Action::{self};
-use keyberon::layout::*;
 
-const fn arrange_layer(input: [Action; 65]) -> [[Action; 13]; 5] {
-    [
-        [
-            input[2], input[3], input[4], input[5], input[6], input[7], input[8], input[9],
-            input[10], input[11], input[12], input[13], input[14],
-        ],
-        [
-            input[1], input[17], input[18], input[19], input[20], input[21], input[23], input[24],
-            input[25], input[26], input[27], input[28], input[29],
-        ],
-        [
-            input[16], input[32], input[33], input[34], input[35], input[36], input[22], input[38],
-            input[39], input[40], input[41], input[42], input[43],
-        ],
-        [
-            input[0], input[31], input[45], input[46], input[47], input[48], input[49], input[37],
-            input[51], input[53], input[54], input[56], input[57],
-        ],
-        [
-            input[15], input[30], input[44], input[58], input[59], input[60], input[61], input[50],
-            input[62], input[52], input[63], input[55], input[64],
-        ],
-    ]
-}
+// The matrix is wired to fit 13x5, so keys aren't where you'd expect them to
+// be. `wiring` has the (row, col) in the matrix for each key on the board.
+#[rustfmt::skip]
+alice_layout! {
+    pub static ALICE_LAYOUT;
+    custom keebifa_keys::action::CustomAction;
 
-const fn convert_layers<const L: usize>(input: [[Action; 65]; L]) -> Layers<13, 5, L> {
-    let mut i = 0;
-    let mut new_layers: [[[Action; 13]; 5]; L] = [[[Action::NoOp; 13]; 5]; L];
-    while i < L {
-        new_layers[i] = arrange_layer(input[i]);
-        i += 1;
+    wiring {
+        [(3, 0) (1, 0) (0, 0) (0, 1) (0, 2) (0, 3) (0, 4) (0, 5) (0, 6) (0, 7) (0, 8) (0, 9) (0, 10) (0, 11) (0, 12)]
+        [(4, 0) (2, 0) (1, 1) (1, 2) (1, 3) (1, 4) (1, 5) (2, 6) (1, 6) (1, 7) (1, 8) (1, 9) (1, 10) (1, 11) (1, 12)]
+        [(4, 1) (3, 1) (2, 1) (2, 2) (2, 3) (2, 4) (2, 5) (3, 7) (2, 7) (2, 8) (2, 9) (2, 10) (2, 11) (2, 12)]
+        [(4, 2) (3, 2) (3, 3) (3, 4) (3, 5) (3, 6) (4, 7) (3, 8) (4, 9) (3, 9) (3, 10) (4, 11) (3, 11) (3, 12)]
+        [(4, 3) (4, 4) (4, 5) (4, 6) (4, 8) (4, 10) (4, 12)]
     }
-    new_layers
-}
-
-#[rustfmt::skip]
-#[allow(dead_code)]
 
-pub static ALICE_LAYOUT: Layers<13, 5, 2> = convert_layers(alice_layout! {
-    {
+    layer base {
         [Escape '`' 1 2 3 4 5 6 7 8 9 0 - = BSpace]
         [PgUp Tab Q W E R T Y U I O P '[' ']' '\\']
         [PgDown LCtrl A S D F G H J K L ; Quote Enter]
-        [LShift Z X C V n B N M , . / RShift n]
-        [n LAlt Space LGui Space RAlt RCtrl]
+        [LShift Z X C V n B N M , . '/' RShift n]
+        [(function) @ "fn" LAlt Space LGui Space RAlt RCtrl]
     }
-    {
-        [ n n n n n n n n n n n n n n n]
-        [ n n n n n n n n n n n n n n n]
-        [ n n n n n n n n n n n n n n]
-        [ n n n n n n n n n n n n n n]
-        [ n n n n n n n]
+    layer function : base @ "F keys on the number row" {
+        1 => F1, 2 => F2, 3 => F3, 4 => F4, 5 => F5, 6 => F6,
+        7 => F7, 8 => F8, 9 => F9, 0 => F10, - => F11, = => F12,
+        P => Play, '[' => VolDown, ']' => VolUp, '\\' => Mute,
+        BSpace => SysSleep,
+        I => MsUp, J => MsLeft, K => MsDown, L => MsRight,
+        U => MsBtn1, O => MsBtn2, H => WhUp, N => WhDown, M => MsSlow,
     }
-});
+}
 
 #[cfg(test)]
 #[test]
 fn alice_layout_test() {
-    pub static ALICE_LAYOUT: Layers<13, 5, 1> = convert_layers(alice_layout! {
-        {
-            [Escape '`' 1 2 3 4 5 6 7 8 9 0 - = BSpace]
-            [PgUp Tab Q W E R T Y U I O P '[' ']' '\\']
-            [PgDown LCtrl A S D F G H J K L ; Quote Enter]
-            [LShift Z X C V n B N M , . / RShift n]
-            [n LAlt Space LGui Space RAlt RCtrl]
-        }
-    });
-    printf!("{:?}", ALICE_LAYOUT);
+    use keebifa_keys::action::CustomAction;
+    use keyberon::action::Action;
+    use keyberon::key_code::KeyCode;
+
+    assert_eq!(ALICE_LAYOUT[0][3][0], Action::KeyCode(KeyCode::Escape));
+    assert_eq!(ALICE_LAYOUT[0][1][0], Action::KeyCode(KeyCode::Grave));
+    assert_eq!(ALICE_LAYOUT[0][0][0], Action::KeyCode(KeyCode::Kb1));
+    assert_eq!(ALICE_LAYOUT[0][2][6], Action::KeyCode(KeyCode::Y));
+
+    assert_eq!(ALICE_LAYOUT[FUNCTION][0][0], Action::KeyCode(KeyCode::F1));
+    assert_eq!(ALICE_LAYOUT[FUNCTION][2][6], Action::KeyCode(KeyCode::Y));
+    assert_eq!(
+        ALICE_LAYOUT[FUNCTION][1][11],
+        Action::Custom(CustomAction::Consumer(0xe9))
+    );
+    assert_eq!(
+        ALICE_LAYOUT[0][ALICE_LAYOUT_SWITCHES][FUNCTION],
+        Action::Layer(FUNCTION)
+    );
 }
@@ -6,6 +6,7 @@
 #![no_std]
 #![no_main]
 
+mod hid;
 mod layout;
 use defmt_rtt as _;
 use panic_halt as _;
@@ -14,7 +15,9 @@ use rtic::app;
 #[app(device = adafruit_kb2040::hal::pac, peripherals = true, dispatchers = [PIO0_IRQ_0])]
 mod app {
 
+    use crate::hid::{BootKeyboard, NkroKeyboard};
     use crate::layout::*;
+    use keebifa_keys::{action::*, mouse::*, report::*};
 
     use adafruit_kb2040::{
         hal::{self, gpio::DynPin, Timer},
@@ -25,16 +28,36 @@ mod app {
     };
     use embedded_time::duration::Extensions;
     use usb_device::{class_prelude::*, prelude::*};
+    use usbd_hid::{
+        descriptor::{MediaKeyboardReport, MouseReport, SerializedDescriptor, SystemControlReport},
+        hid_class::HIDClass,
+    };
 
-    use keyberon::{debounce::Debouncer, key_code::KbHidReport, layout::Layout, matrix::Matrix};
+    use keyberon::{
+        debounce::Debouncer,
+        layout::{CustomEvent, Event, Layout},
+        matrix::Matrix,
+    };
 
     const COL_NUM: usize = 13;
     const ROW_NUM: usize = 5;
 
+    const MOUSE_CONFIG: MouseConfig = MouseConfig {
+        initial_speed: 200,
+        max_speed: 1500,
+        time_to_max: 1000,
+        curve: Curve::Quadratic,
+        precision: 4,
+        wheel_interval: 80,
+    };
+
     #[shared]
     struct Shared {
-        usb_hid:
-            keyberon::hid::HidClass<'static, hal::usb::UsbBus, keyberon::keyboard::Keyboard<()>>,
+        usb_hid: BootKeyboard<'static, hal::usb::UsbBus>,
+        usb_nkro: NkroKeyboard<'static, hal::usb::UsbBus>,
+        usb_consumer: HIDClass<'static, hal::usb::UsbBus>,
+        usb_system: HIDClass<'static, hal::usb::UsbBus>,
+        usb_mouse: HIDClass<'static, hal::usb::UsbBus>,
         usb_dev: usb_device::device::UsbDevice<'static, hal::usb::UsbBus>,
         timer: hal::timer::Timer,
         alarm: hal::timer::Alarm0,
@@ -43,7 +66,19 @@ mod app {
         #[lock_free]
         debouncer: Debouncer<[[bool; COL_NUM]; ROW_NUM]>,
         #[lock_free]
-        layout: Layout<COL_NUM, ROW_NUM, 2>,
+        layout: Layout<COL_NUM, { ROW_NUM + 1 }, ALICE_LAYOUT_LAYER_COUNT, CustomAction>,
+        #[lock_free]
+        layer_state: LayerState,
+        #[lock_free]
+        typist: Typist,
+        #[lock_free]
+        symbols: Symbols,
+        #[lock_free]
+        consumer: ReportKeys<u16>,
+        #[lock_free]
+        system: ReportKeys<u8>,
+        #[lock_free]
+        mouse: MouseKeys,
         #[lock_free]
         watchdog: hal::watchdog::Watchdog,
     }
@@ -86,13 +121,20 @@ mod app {
                 &mut resets,
             )));
 
-        let usb_hid = keyberon::new_class(usb_bus, ());
+        let usb_hid = BootKeyboard::new(usb_bus);
+        let usb_nkro = NkroKeyboard::new(usb_bus);
+        // Media, system and mouse keys aren't keycodes, so they have their
+        // own interfaces
+        let usb_consumer = HIDClass::new(usb_bus, MediaKeyboardReport::desc(), 10);
+        let usb_system = HIDClass::new(usb_bus, SystemControlReport::desc(), 10);
+        let usb_mouse = HIDClass::new(usb_bus, MouseReport::desc(), 1);
 
         let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
             .manufacturer("ifacodes")
             .product("keebifa Keyboard")
             .serial_number("ifapersonal")
-            .device_class(0x02)
+            // Each interface gives its own class, as there's more than one
+            .device_class(0x00)
             .build();
 
         // Initalize pins and keyboard matrix.
@@ -154,12 +196,22 @@ mod app {
         (
             Shared {
                 usb_hid,
+                usb_nkro,
+                usb_consumer,
+                usb_system,
+                usb_mouse,
                 usb_dev,
                 timer,
                 alarm,
                 matrix,
                 debouncer,
                 layout,
+                layer_state: LayerState::new(ALICE_LAYOUT_SWITCHES, COL_NUM),
+                typist: Typist::new(),
+                symbols: Symbols::new(),
+                consumer: ReportKeys::new(),
+                system: ReportKeys::new(),
+                mouse: MouseKeys::new(MOUSE_CONFIG),
                 watchdog,
             },
             Local {},
@@ -167,7 +219,7 @@ mod app {
         )
     }
 
-    #[task(binds = TIMER_IRQ_0, priority = 1, shared = [usb_hid, timer, alarm, matrix, debouncer, layout, watchdog])]
+    #[task(binds = TIMER_IRQ_0, priority = 1, shared = [usb_hid, usb_nkro, usb_consumer, usb_system, usb_mouse, timer, alarm, matrix, debouncer, layout, layer_state, typist, symbols, consumer, system, mouse, watchdog])]
     fn timer_irq(mut cx: timer_irq::Context) {
         // Clear Interrupt
         let mut alarm = cx.shared.alarm;
@@ -178,30 +230,164 @@ mod app {
 
         cx.shared.watchdog.feed();
 
+        let mut pressed = false;
         for event in cx.shared.debouncer.events(cx.shared.matrix.get().unwrap()) {
+            if let Event::Press(row, col) = event {
+                let layer = cx.shared.layout.current_layer();
+                defmt::debug!(
+                    "{=str} pressed on {=str}",
+                    ALICE_LAYOUT_LABELS[layer][row as usize][col as usize],
+                    ALICE_LAYOUT_LAYERS[layer].0
+                );
+            }
+            pressed |= event.is_press();
             cx.shared.layout.event(event);
         }
-        cx.shared.layout.tick();
-
-        let report: KbHidReport = cx.shared.layout.keycodes().collect();
-        if cx
-            .shared
-            .usb_hid
-            .lock(|h| h.device_mut().set_keyboard_report(report.clone()))
-        {
-            while let Ok(0) = cx.shared.usb_hid.lock(|h| h.write(report.as_bytes())) {}
+        let custom = cx.shared.layout.tick();
+
+        if pressed {
+            // The key has already been pressed on the one-shot layer by now
+            cx.shared.layer_state.key_pressed();
+        }
+        match custom {
+            CustomEvent::Press(CustomAction::TypeString(chords)) => cx.shared.typist.start(chords),
+            CustomEvent::Press(CustomAction::Symbol(chord)) => cx.shared.symbols.press(chord),
+            CustomEvent::Press(CustomAction::DefaultLayer(layer)) => {
+                cx.shared.layer_state.set_default(*layer)
+            }
+            CustomEvent::Press(CustomAction::ToggleLayer(layer)) => {
+                cx.shared.layer_state.toggle(*layer)
+            }
+            CustomEvent::Press(CustomAction::OneShotLayer(layer)) => {
+                cx.shared.layer_state.one_shot(*layer)
+            }
+            CustomEvent::Press(CustomAction::Consumer(usage)) => cx.shared.consumer.press(*usage),
+            CustomEvent::Release(CustomAction::Consumer(usage)) => {
+                cx.shared.consumer.release(*usage)
+            }
+            CustomEvent::Press(CustomAction::System(usage)) => cx.shared.system.press(*usage),
+            CustomEvent::Release(CustomAction::System(usage)) => cx.shared.system.release(*usage),
+            CustomEvent::Press(CustomAction::MouseMove(x, y)) => cx.shared.mouse.press_move(*x, *y),
+            CustomEvent::Release(CustomAction::MouseMove(x, y)) => {
+                cx.shared.mouse.release_move(*x, *y)
+            }
+            CustomEvent::Press(CustomAction::MouseButton(buttons)) => {
+                cx.shared.mouse.press_buttons(*buttons)
+            }
+            CustomEvent::Release(CustomAction::MouseButton(buttons)) => {
+                cx.shared.mouse.release_buttons(*buttons)
+            }
+            CustomEvent::Press(CustomAction::MouseWheel(wheel, pan)) => {
+                cx.shared.mouse.press_wheel(*wheel, *pan)
+            }
+            CustomEvent::Release(CustomAction::MouseWheel(wheel, pan)) => {
+                cx.shared.mouse.release_wheel(*wheel, *pan)
+            }
+            CustomEvent::Press(CustomAction::MousePrecision) => cx.shared.mouse.press_precision(),
+            CustomEvent::Release(CustomAction::MousePrecision) => {
+                cx.shared.mouse.release_precision()
+            }
+            _ => (),
+        }
+        cx.shared
+            .layout
+            .set_default_layer(cx.shared.layer_state.default_layer());
+        for event in cx.shared.layer_state.events() {
+            cx.shared.layout.event(event);
+        }
+
+        // Keys held while a string is being typed would change what gets typed
+        let keys: NkroReport = if cx.shared.typist.is_typing() {
+            cx.shared.typist.report().iter().copied().collect()
+        } else {
+            cx.shared
+                .symbols
+                .keycodes(cx.shared.layout.keycodes())
+                .collect()
+        };
+
+        // Keys are only sent from one of the keyboards, so the other one is
+        // left empty. A host that's asked for the boot protocol only knows
+        // boot keyboards, so it always gets the boot keyboard.
+        let protocol = cx.shared.usb_hid.lock(|h| h.protocol());
+        let use_nkro =
+            protocol == ReportProtocol::Report && cx.shared.usb_nkro.lock(|n| n.is_used());
+        let (report, nkro) = match use_nkro {
+            true => ([0; 8], keys),
+            false => (keys.boot_report(protocol), NkroReport::default()),
+        };
+        let boot_sent = cx.shared.usb_hid.lock(|h| h.send(report));
+        let nkro_sent = cx.shared.usb_nkro.lock(|n| n.send(*nkro.as_bytes()));
+        let sent = match use_nkro {
+            true => nkro_sent,
+            false => boot_sent,
+        };
+        // A string or symbol is only typed as fast as the host takes the
+        // reports, so none of them are lost and no chord is left held
+        if sent && cx.shared.typist.is_typing() {
+            cx.shared.typist.sent();
+        } else if sent {
+            cx.shared.symbols.sent();
+        }
+
+        if let Some(usage_id) = cx.shared.consumer.report() {
+            let report = MediaKeyboardReport { usage_id };
+            if cx
+                .shared
+                .usb_consumer
+                .lock(|c| c.push_input(&report))
+                .is_ok()
+            {
+                cx.shared.consumer.sent(usage_id);
+            }
+        }
+        if let Some(usage_id) = cx.shared.system.report() {
+            let report = SystemControlReport { usage_id };
+            if cx.shared.usb_system.lock(|s| s.push_input(&report)).is_ok() {
+                cx.shared.system.sent(usage_id);
+            }
+        }
+
+        // Ticked every ms, whatever the scan rate, so the speed stays the same
+        cx.shared.mouse.tick();
+        if let Some(report) = cx.shared.mouse.report() {
+            let mouse_report = MouseReport {
+                buttons: report.buttons,
+                x: report.x,
+                y: report.y,
+                wheel: report.wheel,
+                pan: report.pan,
+            };
+            if cx
+                .shared
+                .usb_mouse
+                .lock(|m| m.push_input(&mouse_report))
+                .is_ok()
+            {
+                cx.shared.mouse.sent(&report);
+            }
         }
     }
 
-    #[task(binds = USBCTRL_IRQ, priority = 3, shared = [usb_hid, usb_dev])]
+    #[task(binds = USBCTRL_IRQ, priority = 3, shared = [usb_hid, usb_nkro, usb_consumer, usb_system, usb_mouse, usb_dev])]
     fn usb_rx(cx: usb_rx::Context) {
         let usb_hid = cx.shared.usb_hid;
+        let usb_nkro = cx.shared.usb_nkro;
+        let usb_consumer = cx.shared.usb_consumer;
+        let usb_system = cx.shared.usb_system;
+        let usb_mouse = cx.shared.usb_mouse;
         let usb_dev = cx.shared.usb_dev;
 
-        (usb_hid, usb_dev).lock(|h, d| {
-            if d.poll(&mut [h]) {
-                h.poll();
-            }
-        });
+        (
+            usb_hid,
+            usb_nkro,
+            usb_consumer,
+            usb_system,
+            usb_mouse,
+            usb_dev,
+        )
+            .lock(|h, n, c, s, m, d| {
+                d.poll(&mut [h, n, c, s, m]);
+            });
     }
 }